@@ -0,0 +1,137 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use tower_defense_plugin::MapKind;
+
+#[derive(Parser)]
+#[command(version, about = "A tower defense game")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Map file to play on, the default fixed path map is used otherwise
+    #[arg(long, global = true)]
+    pub map: Option<PathBuf>,
+
+    /// Seed of the creep random generator
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Address the server listens on
+    #[arg(long, global = true)]
+    pub bind: Option<SocketAddr>,
+
+    /// Maximum number of clients connected to the server
+    #[arg(long, global = true)]
+    pub max_clients: Option<usize>,
+
+    /// Fixed game updates per second, independent of the frame rate when set.
+    /// Also paces the server loop and the simulation
+    #[arg(long, global = true)]
+    pub tick_rate: Option<f64>,
+
+    /// JSON file providing defaults for the options above
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Play the game (default)
+    Play,
+    /// Run a headless game server
+    Server,
+    /// Run games headlessly at max speed and report balance statistics
+    Simulate {
+        /// JSON file listing the turrets to place
+        turrets: PathBuf,
+        /// JSON file listing the creep waves
+        waves: PathBuf,
+        /// Number of games to run, each one with the next seed
+        #[arg(long, default_value_t = 1)]
+        games: u32,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// Write the report to this file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Generate a random map from the seed and write it to a map file
+    Generate {
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = GeneratedKind::Simple)]
+        kind: GeneratedKind,
+        /// Minimum number of cells creeps walk through
+        #[arg(long, default_value_t = 25)]
+        length: usize,
+        /// Share of the cells of free maps covered by obstacles
+        #[arg(long, default_value_t = 0.1)]
+        obstacles: f32,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GeneratedKind {
+    Simple,
+    Free,
+    Hex,
+}
+
+impl From<GeneratedKind> for MapKind {
+    fn from(kind: GeneratedKind) -> Self {
+        match kind {
+            GeneratedKind::Simple => MapKind::Simple,
+            GeneratedKind::Free => MapKind::Free,
+            GeneratedKind::Hex => MapKind::Hex,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+/// Options which can be given through `--config`, the command line takes precedence
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub map: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub bind: Option<SocketAddr>,
+    pub max_clients: Option<usize>,
+    pub tick_rate: Option<f64>,
+}
+
+impl Config {
+    pub fn load(path: &PathBuf) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|err| format!("Could not read config {path:?}: {err}"))?;
+        serde_json::from_str(&json).map_err(|err| format!("Invalid config {path:?}: {err}"))
+    }
+}
+
+impl Cli {
+    /// Fill the options missing from the command line with the config file
+    pub fn resolve(mut self) -> Result<Self, String> {
+        if let Some(path) = &self.config {
+            let config = Config::load(path)?;
+            self.map = self.map.or(config.map);
+            self.seed = self.seed.or(config.seed);
+            self.bind = self.bind.or(config.bind);
+            self.max_clients = self.max_clients.or(config.max_clients);
+            self.tick_rate = self.tick_rate.or(config.tick_rate);
+        }
+
+        match self.tick_rate {
+            Some(tick_rate) if !(tick_rate.is_finite() && tick_rate > 0.0) => Err(format!(
+                "Tick rate must be positive and finite, got {tick_rate}"
+            )),
+            _ => Ok(self),
+        }
+    }
+}
@@ -1,38 +1,144 @@
-use std::env;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
 
 use bevy::{app::*, *};
-use tower_defense_gui::{TowerDefenseGui, TowerDefenseGuiSimpleMap};
-use tower_defense_plugin::{TowerDefensePlugin, TowerDefensePluginSimpleMap};
+use clap::Parser;
+use cli::{Cli, Command};
+use tower_defense_gui::TowerDefenseGui;
+use tower_defense_plugin::config::MapSource;
+use tower_defense_plugin::map::generator::MapGenerator;
+use tower_defense_plugin::{MapFile, MapKind, TowerDefensePlugin};
 use tower_defense_server::ServerPlugin;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() > 1 {
-        if args[1].as_str() == "freemap" {
-            println!("Running FreeMap mode");
-            App::new()
-                .add_plugins(DefaultPlugins)
-                .add_plugins(TowerDefensePlugin)
-                .add_plugins(TowerDefenseGui)
-                .run();
-        } else if args[1].as_str() == "server" {
-            println!("Running Server mode");
-            App::new()
-                .add_plugins(MinimalPlugins)
-                .add_plugins(TowerDefensePluginSimpleMap)
-                .add_plugins(ServerPlugin)
-                .run();
-        } else {
-            println!("Unrecognized argument {:?}", args[1].as_str());
-            println!("Valid arguments are: freemap");
+mod cli;
+mod simulate;
+
+const DEFAULT_TICK_RATE: f64 = 60.0;
+
+fn main() -> ExitCode {
+    let cli = match Cli::parse().resolve() {
+        Ok(cli) => cli,
+        Err(err) => {
+            println!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match cli.command {
+        None | Some(Command::Play) => play(&cli),
+        Some(Command::Server) => server(&cli),
+        Some(Command::Simulate {
+            ref turrets,
+            ref waves,
+            games,
+            format,
+            ref output,
+        }) => match &cli.map {
+            Some(map) => {
+                println!("Running Simulation mode");
+                simulate::run(simulate::SimulationOptions {
+                    map: map.clone(),
+                    turrets: turrets.clone(),
+                    waves: waves.clone(),
+                    games,
+                    seed: cli.seed.unwrap_or(0),
+                    tick_rate: cli.tick_rate.unwrap_or(DEFAULT_TICK_RATE),
+                    format,
+                    output: output.clone(),
+                })
+            }
+            None => Err(String::from("simulate requires a --map")),
+        },
+        Some(Command::Generate {
+            ref output,
+            kind,
+            length,
+            obstacles,
+        }) => generate(&cli, output, kind.into(), length, obstacles),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            println!("{err}");
+            ExitCode::FAILURE
         }
-    } else {
-        println!("Running FixedPathMap mode");
-        App::new()
-            .add_plugins(DefaultPlugins)
-            .add_plugins(TowerDefensePluginSimpleMap)
-            .add_plugins(TowerDefenseGuiSimpleMap)
-            .run();
     }
 }
+
+fn play(cli: &Cli) -> Result<(), String> {
+    // The editor starts from the played map but saves to its own file
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(game_plugin(cli)?)
+        .add_plugins(TowerDefenseGui::default())
+        .run();
+    Ok(())
+}
+
+fn server(cli: &Cli) -> Result<(), String> {
+    println!("Running Server mode");
+    let tick_rate = cli.tick_rate.unwrap_or(DEFAULT_TICK_RATE);
+
+    let mut server = ServerPlugin::default();
+    if let Some(bind) = cli.bind {
+        server.bind = bind;
+    }
+    if let Some(max_clients) = cli.max_clients {
+        server.max_clients = max_clients;
+    }
+
+    App::new()
+        .add_plugins(
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1.0 / tick_rate,
+            ))),
+        )
+        .add_plugins(game_plugin(cli)?)
+        .add_plugins(server)
+        .run();
+    Ok(())
+}
+
+fn generate(
+    cli: &Cli,
+    output: &Path,
+    kind: MapKind,
+    length: usize,
+    obstacles: f32,
+) -> Result<(), String> {
+    let seed = cli.seed.unwrap_or(0);
+    let map_file = MapGenerator::new(kind, seed)
+        .with_path_length(length)
+        .with_obstacle_density(obstacles)
+        .generate()?;
+    map_file
+        .save(output)
+        .map_err(|err| format!("Could not write map {output:?}: {err}"))?;
+    println!("Generated {kind:?} map {output:?} from seed {seed}");
+    Ok(())
+}
+
+fn game_plugin(cli: &Cli) -> Result<TowerDefensePlugin, String> {
+    let mut plugin = TowerDefensePlugin::default();
+    match &cli.map {
+        Some(path) => {
+            let map_file =
+                MapFile::load(path).map_err(|err| format!("Invalid map {path:?}: {err}"))?;
+            map_file
+                .validate()
+                .map_err(|err| format!("Invalid map {path:?}: {err}"))?;
+            println!("Running {:?} map {path:?}", map_file.kind);
+            plugin = plugin.with_map(MapSource::Loaded(map_file));
+        }
+        None => println!("Running default FixedPathMap"),
+    }
+    if let Some(seed) = cli.seed {
+        plugin = plugin.with_seed(seed);
+    }
+    if let Some(tick_rate) = cli.tick_rate {
+        plugin = plugin.with_tick_rate(tick_rate);
+    }
+    Ok(plugin)
+}
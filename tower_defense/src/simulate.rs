@@ -0,0 +1,347 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cli::Format;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use serde::{Deserialize, Serialize};
+use tower_defense_plugin::components::{Creep, Turret, TurretType};
+use tower_defense_plugin::config::MapSource;
+use tower_defense_plugin::events::*;
+use tower_defense_plugin::resources::{GameData, SpawnTimer, Waves};
+use tower_defense_plugin::{MapFile, TowerDefensePlugin};
+
+/// Games still running after this many simulated seconds are stopped
+const MAX_GAME_TIME: f32 = 3600.0;
+/// Simulated seconds between two samples of the gold curve
+const GOLD_SAMPLE_PERIOD: f32 = 1.0;
+
+pub struct SimulationOptions {
+    pub map: PathBuf,
+    pub turrets: PathBuf,
+    pub waves: PathBuf,
+    pub games: u32,
+    pub seed: u64,
+    /// Simulated updates per second
+    pub tick_rate: f64,
+    pub format: Format,
+    /// Write the report to this file instead of stdout
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct TurretPlacement {
+    turret_type: TurretType,
+    position: IVec2,
+}
+
+#[derive(Default, Serialize)]
+struct WaveReport {
+    spawned: u32,
+    killed: u32,
+    leaked: u32,
+    mean_time_to_kill: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct TurretTypeReport {
+    turrets: usize,
+    damage: f32,
+    dps: f32,
+    dps_per_turret: f32,
+}
+
+#[derive(Serialize)]
+struct GameReport {
+    seed: u64,
+    duration: f32,
+    lives: i32,
+    turrets_placed: usize,
+    waves: Vec<WaveReport>,
+    turret_types: BTreeMap<String, TurretTypeReport>,
+    /// (time, gold) samples
+    gold_curve: Vec<(f32, i32)>,
+}
+
+#[derive(Resource, Default)]
+struct SimulationStats {
+    spawn_times: HashMap<Entity, f32>,
+    waves: Vec<WaveReport>,
+    kill_times: Vec<Vec<f32>>,
+    damage: HashMap<TurretType, f32>,
+    gold_curve: Vec<(f32, i32)>,
+}
+
+impl SimulationStats {
+    fn wave(&mut self, wave: usize) -> &mut WaveReport {
+        if self.waves.len() <= wave {
+            self.waves.resize_with(wave + 1, WaveReport::default);
+            self.kill_times.resize_with(wave + 1, Vec::new);
+        }
+        &mut self.waves[wave]
+    }
+}
+
+pub fn run(options: SimulationOptions) -> Result<(), String> {
+    let map_file = MapFile::load(&options.map)?;
+    map_file
+        .validate()
+        .map_err(|err| format!("Invalid map {:?}: {err}", options.map))?;
+    let waves = Waves::load(&options.waves)?;
+    let layout_json = fs::read_to_string(&options.turrets).map_err(|err| err.to_string())?;
+    let layout: Vec<TurretPlacement> =
+        serde_json::from_str(&layout_json).map_err(|err| err.to_string())?;
+
+    let reports: Vec<GameReport> = (0..options.games)
+        .map(|game| {
+            let seed = options.seed + game as u64;
+            run_game(map_file.clone(), &layout, &waves, seed, options.tick_rate)
+        })
+        .collect();
+
+    let report = match options.format {
+        Format::Json => serde_json::to_string_pretty(&reports).map_err(|err| err.to_string())?,
+        Format::Csv => to_csv(&reports),
+    };
+    match options.output {
+        Some(path) => fs::write(path, report).map_err(|err| err.to_string()),
+        None => {
+            println!("{report}");
+            Ok(())
+        }
+    }
+}
+
+fn run_game(
+    map: MapFile,
+    layout: &[TurretPlacement],
+    waves: &Waves,
+    seed: u64,
+    tick_rate: f64,
+) -> GameReport {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(
+            TowerDefensePlugin::default()
+                .with_map(MapSource::Loaded(map))
+                .with_seed(seed),
+        )
+        .insert_resource(waves.clone())
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / tick_rate,
+        )))
+        .init_resource::<SimulationStats>()
+        .add_systems(
+            Last,
+            (record_spawns, record_damage, record_kills, record_leaks),
+        );
+
+    for placement in layout {
+        app.world_mut().write_message(PlaceTurretMessage {
+            turret_type: placement.turret_type,
+            position: placement.position,
+        });
+    }
+
+    let mut next_gold_sample = 0.0;
+    loop {
+        app.update();
+
+        let world = app.world_mut();
+        let elapsed = world.resource::<Time>().elapsed_secs();
+        if elapsed >= next_gold_sample {
+            let gold = world.resource::<GameData>().gold;
+            world
+                .resource_mut::<SimulationStats>()
+                .gold_curve
+                .push((elapsed, gold));
+            next_gold_sample += GOLD_SAMPLE_PERIOD;
+        }
+
+        let waves_done = world.resource::<SpawnTimer>().wave >= waves.waves.len();
+        let creeps_left = world.query::<&Creep>().iter(world).count();
+        if (waves_done && creeps_left == 0)
+            || world.resource::<GameData>().lives <= 0
+            || elapsed >= MAX_GAME_TIME
+        {
+            break;
+        }
+    }
+
+    build_report(app.world_mut(), seed, waves.waves.len())
+}
+
+fn build_report(world: &mut World, seed: u64, wave_count: usize) -> GameReport {
+    let duration = world.resource::<Time>().elapsed_secs();
+    let lives = world.resource::<GameData>().lives;
+
+    let mut turret_counts: HashMap<TurretType, usize> = HashMap::new();
+    for turret in world.query::<&Turret>().iter(world) {
+        *turret_counts.entry(turret.turret_type).or_default() += 1;
+    }
+
+    let mut stats = world.resource_mut::<SimulationStats>();
+    if wave_count > 0 {
+        stats.wave(wave_count - 1);
+    }
+    let kill_times = std::mem::take(&mut stats.kill_times);
+    for (wave, times) in stats.waves.iter_mut().zip(kill_times) {
+        if !times.is_empty() {
+            wave.mean_time_to_kill = Some(times.iter().sum::<f32>() / times.len() as f32);
+        }
+    }
+
+    let turret_types = turret_counts
+        .iter()
+        .map(|(turret_type, &turrets)| {
+            let damage = stats.damage.get(turret_type).copied().unwrap_or(0.0);
+            let dps = if duration > 0.0 {
+                damage / duration
+            } else {
+                0.0
+            };
+            (
+                format!("{turret_type:?}"),
+                TurretTypeReport {
+                    turrets,
+                    damage,
+                    dps,
+                    dps_per_turret: dps / turrets as f32,
+                },
+            )
+        })
+        .collect();
+
+    GameReport {
+        seed,
+        duration,
+        lives,
+        turrets_placed: turret_counts.values().sum(),
+        waves: std::mem::take(&mut stats.waves),
+        turret_types,
+        gold_curve: std::mem::take(&mut stats.gold_curve),
+    }
+}
+
+fn to_csv(reports: &[GameReport]) -> String {
+    let mut csv = String::from("game,metric,key,value\n");
+    for (game, report) in reports.iter().enumerate() {
+        csv += &format!("{game},duration,,{}\n", report.duration);
+        csv += &format!("{game},lives,,{}\n", report.lives);
+        for (wave, wave_report) in report.waves.iter().enumerate() {
+            csv += &format!("{game},spawned,{wave},{}\n", wave_report.spawned);
+            csv += &format!("{game},killed,{wave},{}\n", wave_report.killed);
+            csv += &format!("{game},leaked,{wave},{}\n", wave_report.leaked);
+            if let Some(time_to_kill) = wave_report.mean_time_to_kill {
+                csv += &format!("{game},mean_time_to_kill,{wave},{time_to_kill}\n");
+            }
+        }
+        for (turret_type, turret_report) in &report.turret_types {
+            csv += &format!("{game},dps,{turret_type},{}\n", turret_report.dps);
+            csv += &format!(
+                "{game},dps_per_turret,{turret_type},{}\n",
+                turret_report.dps_per_turret
+            );
+        }
+        for (time, gold) in &report.gold_curve {
+            csv += &format!("{game},gold,{time},{gold}\n");
+        }
+    }
+    csv
+}
+
+fn record_spawns(
+    creeps: Query<(Entity, &Creep), Added<Creep>>,
+    time: Res<Time>,
+    mut stats: ResMut<SimulationStats>,
+) {
+    for (entity, creep) in &creeps {
+        stats.spawn_times.insert(entity, time.elapsed_secs());
+        stats.wave(creep.wave).spawned += 1;
+    }
+}
+
+fn record_damage(
+    mut events: MessageReader<CreepDamagedMessage>,
+    mut stats: ResMut<SimulationStats>,
+) {
+    for event in events.read() {
+        *stats.damage.entry(event.turret_type).or_default() += event.damage;
+    }
+}
+
+fn record_kills(
+    mut events: MessageReader<CreepKilledMessage>,
+    time: Res<Time>,
+    mut stats: ResMut<SimulationStats>,
+) {
+    for event in events.read() {
+        stats.wave(event.wave).killed += 1;
+        if let Some(spawn_time) = stats.spawn_times.remove(&event.creep) {
+            stats.kill_times[event.wave].push(time.elapsed_secs() - spawn_time);
+        }
+    }
+}
+
+fn record_leaks(mut events: MessageReader<CreepLeakedMessage>, mut stats: ResMut<SimulationStats>) {
+    for event in events.read() {
+        stats.wave(event.wave).leaked += 1;
+        stats.spawn_times.remove(&event.creep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_defense_plugin::resources::Wave;
+    use tower_defense_plugin::{BaseMap, MapKind};
+
+    fn one_wave_game() -> GameReport {
+        let map = MapFile {
+            kind: MapKind::Free,
+            map: BaseMap::default(),
+        };
+        let layout = [TurretPlacement {
+            turret_type: TurretType::Basic,
+            position: IVec2::new(4, 4),
+        }];
+        let waves = Waves {
+            waves: vec![Wave {
+                count: 3,
+                interval: 2.0,
+                delay: 0.0,
+                health: 10.0,
+                speed: 5.0,
+                bounty: 1,
+                health_variance: 0.0,
+            }],
+        };
+        run_game(map, &layout, &waves, 1, 20.0)
+    }
+
+    #[test]
+    fn reports_a_headless_game() {
+        let report = one_wave_game();
+        assert_eq!(report.turrets_placed, 1);
+        assert_eq!(report.waves.len(), 1);
+        let wave = &report.waves[0];
+        assert_eq!(wave.spawned, 3);
+        assert_eq!(wave.killed + wave.leaked, 3);
+        assert!(wave.killed > 0);
+        assert!(wave.mean_time_to_kill.is_some_and(|time| time > 0.0));
+
+        // Overkill is not counted, each killed creep had 10 health
+        let basic = &report.turret_types["Basic"];
+        assert_eq!(basic.turrets, 1);
+        assert!(basic.damage >= 10.0 * wave.killed as f32 - 1e-3);
+        assert!((basic.dps - basic.damage / report.duration).abs() < 1e-4);
+        assert_eq!(basic.dps_per_turret, basic.dps);
+
+        let csv = to_csv(&[report]);
+        assert!(csv.starts_with("game,metric,key,value\n"));
+        assert!(csv.contains("0,spawned,0,3\n"));
+        assert!(csv.contains("0,dps,Basic,"));
+    }
+}
@@ -3,6 +3,37 @@ use bevy::{prelude::*, sprite::Anchor};
 #[derive(Component)]
 pub struct Path {}
 
+#[derive(Component)]
+pub struct Endpoint {}
+
+#[derive(Component)]
+pub struct TurretMesh;
+
+/// Turret mesh rotated along the facing of the turret at `position`
+#[derive(Component)]
+pub struct AimedTurret {
+    pub position: IVec2,
+}
+
+#[derive(Component)]
+pub struct PreviewCell;
+
+/// Path and length delta the creeps would have with the hovered placement
+#[derive(Component)]
+pub struct GhostPath;
+
+/// Stats of the turret under the cursor
+#[derive(Component)]
+pub struct TurretInfo;
+
+/// Ground of the map, one hexagon per cell or a single rectangle
+#[derive(Component)]
+pub struct MapTile;
+
+/// Cells, endpoints and waypoints drawn by the map editor
+#[derive(Component)]
+pub struct EditorTile;
+
 #[derive(Component)]
 pub struct MainCamera;
 
@@ -25,6 +56,12 @@ pub struct FireBundle {
     pub anchor: Anchor,
 }
 
+/// Line drawn from a beam turret to the creep it is firing at
+#[derive(Component)]
+pub struct BeamLine {
+    pub turret: Entity,
+}
+
 #[derive(Component)]
 pub struct AnimationIndices {
     pub(crate) first: usize,
@@ -0,0 +1,271 @@
+use std::fs;
+
+use bevy::prelude::*;
+use tower_defense_plugin::resources::GridShape;
+use tower_defense_plugin::save::SwitchMap;
+use tower_defense_plugin::*;
+
+use crate::components::EditorTile;
+use crate::resources::*;
+use crate::systems::{CursorCell, MapFrame, path_line};
+
+/// Drawn above the paused game
+const EDITOR_Z: f32 = 200.0;
+
+pub fn not_editing(editor: Res<MapEditor>) -> bool {
+    !editor.active
+}
+
+/// E edits the current map, and plays the edited map once it is valid
+pub fn toggle_editor(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    map: Res<ActiveMap>,
+    mut editor: ResMut<MapEditor>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyE) {
+        return;
+    }
+
+    if !editor.active {
+        let mut file = map.snapshot();
+        // Towers are not part of the map
+        for cell in file.map.cells.iter_mut().flatten() {
+            if *cell == u8::MAX {
+                *cell = 0;
+            }
+        }
+        editor.active = true;
+        editor.open(file);
+        time.pause();
+        println!("Editing map, E to play it");
+    } else if let Some(err) = &editor.error {
+        println!("Can not play an invalid map: {err}");
+    } else {
+        commands.queue(SwitchMap(ActiveMap::from(editor.file.clone())));
+        editor.active = false;
+        time.unpause();
+        println!("Playing the edited map");
+    }
+}
+
+/// Keys pick the tool, the mouse paints cells and places the endpoints and waypoints
+pub fn editor_input(
+    mut commands: Commands,
+    cursor: CursorCell,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<MapEditor>,
+) {
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        if keys.just_pressed(KeyCode::KeyS) {
+            save_map(&editor);
+        } else if keys.just_pressed(KeyCode::KeyO) {
+            load_map(&mut commands, &mut editor);
+        }
+        return;
+    }
+
+    for (key, tool) in [
+        (KeyCode::KeyR, EditorTool::Road),
+        (KeyCode::KeyW, EditorTool::Obstacle),
+        (KeyCode::KeyB, EditorTool::Buildable),
+        (KeyCode::KeyS, EditorTool::Start),
+        (KeyCode::KeyG, EditorTool::End),
+        (KeyCode::KeyP, EditorTool::Waypoint),
+    ] {
+        if keys.just_pressed(key) {
+            editor.tool = tool;
+            println!("Editor tool: {tool:?}");
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyA) {
+        editor.auto_waypoints = !editor.auto_waypoints;
+        println!("Automatic waypoints: {}", editor.auto_waypoints);
+        editor.refresh();
+    }
+    // Both kinds share the square grid
+    if keys.just_pressed(KeyCode::KeyK) {
+        let kind = match editor.file.kind {
+            MapKind::Simple => MapKind::Free,
+            MapKind::Free => MapKind::Simple,
+            MapKind::Hex => MapKind::Hex,
+        };
+        editor.file.kind = kind;
+        println!("Editing a {kind:?} map");
+        editor.refresh();
+    }
+
+    let Some(pos) = cursor.get() else {
+        return;
+    };
+    let Some(content) = editor.file.map.cell(&pos) else {
+        return;
+    };
+    let tool = editor.tool;
+    match tool {
+        EditorTool::Road | EditorTool::Obstacle | EditorTool::Buildable => {
+            let value = match tool {
+                EditorTool::Road => ROAD,
+                EditorTool::Obstacle => OBSTACLE,
+                _ => 0,
+            };
+            // Painting while the button is held
+            if buttons.pressed(MouseButton::Left) && content != value {
+                editor.file.map.cells[pos.x as usize][pos.y as usize] = value;
+                editor.refresh();
+            }
+        }
+        EditorTool::Start | EditorTool::End => {
+            if buttons.just_pressed(MouseButton::Left) {
+                if tool == EditorTool::Start {
+                    editor.file.map.start = pos;
+                } else {
+                    editor.file.map.end = pos;
+                }
+                editor.refresh();
+            }
+        }
+        EditorTool::Waypoint => {
+            // Left click appends a waypoint, right click removes the one under the cursor
+            if buttons.just_pressed(MouseButton::Left) {
+                editor.auto_waypoints = false;
+                editor.file.map.path.push(pos);
+                editor.refresh();
+            } else if buttons.just_pressed(MouseButton::Right) {
+                editor.auto_waypoints = false;
+                let path = &mut editor.file.map.path;
+                match path.iter().rposition(|p| *p == pos) {
+                    Some(index) => {
+                        path.remove(index);
+                    }
+                    None => {
+                        path.pop();
+                    }
+                }
+                editor.refresh();
+            }
+        }
+    }
+}
+
+fn save_map(editor: &MapEditor) {
+    let path = &editor.path;
+    if let Err(err) = editor.file.validate() {
+        println!("Saving an invalid map: {err}");
+    }
+    let saved = match path.parent() {
+        Some(dir) => fs::create_dir_all(dir).map_err(|err| err.to_string()),
+        None => Ok(()),
+    }
+    .and_then(|()| editor.file.save(path));
+    match saved {
+        Ok(()) => println!("Map saved to {path:?}"),
+        Err(err) => println!("Could not write map {path:?}: {err}"),
+    }
+}
+
+fn load_map(commands: &mut Commands, editor: &mut MapEditor) {
+    let path = editor.path.clone();
+    let file = match MapFile::load(&path) {
+        Ok(file) => file,
+        Err(err) => {
+            println!("Could not load map {path:?}: {err}");
+            return;
+        }
+    };
+    // The grid is laid out for the played map, play the loaded one to change its shape
+    if file.kind.shape() != editor.file.kind.shape() {
+        if let Err(err) = file.validate() {
+            println!("Can not edit {path:?} on this grid: {err}");
+            return;
+        }
+        commands.queue(SwitchMap(ActiveMap::from(file.clone())));
+    }
+    editor.open(file);
+    println!("Map loaded from {path:?}");
+}
+
+/// Cells of the edited map, its endpoints and the path the creeps would follow
+pub fn draw_editor(
+    mut commands: Commands,
+    editor: Res<MapEditor>,
+    map_frame: MapFrame,
+    path_assets: Res<PathAssets>,
+    tower_assets: Res<TowerAssets>,
+    q_tiles: Query<Entity, With<EditorTile>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    q_tiles.iter().for_each(|e| commands.entity(e).despawn());
+
+    let Some(layout) = map_frame.layout().filter(|_| editor.active) else {
+        return;
+    };
+    let base = &editor.file.map;
+
+    let grid_rect = layout.grid_rect(IVec2::new(GRID_WIDTH as i32, GRID_HEIGHT as i32));
+    commands.spawn((
+        Mesh2d(meshes.add(Rectangle::from_size(grid_rect.size()))),
+        MeshMaterial2d(path_assets.grid_material.clone()),
+        Transform::from_translation(grid_rect.center().extend(EDITOR_Z)),
+        EditorTile,
+    ));
+
+    let mesh = match layout.shape {
+        GridShape::Square => &tower_assets.square_mesh,
+        GridShape::Hex => &tower_assets.hex_mesh,
+    };
+    for x in 0..GRID_WIDTH as i32 {
+        for y in 0..GRID_HEIGHT as i32 {
+            let pos = IVec2::new(x, y);
+            let material = match base.cell(&pos) {
+                Some(ROAD) => &path_assets.road_material,
+                Some(0) => &path_assets.tile_material,
+                _ => &path_assets.obstacle_material,
+            };
+            commands.spawn((
+                Mesh2d(mesh.clone()),
+                MeshMaterial2d(material.clone()),
+                Transform::from_translation(layout.grid_to_world(pos).extend(EDITOR_Z + 1.0)),
+                EditorTile,
+            ));
+        }
+    }
+
+    for pos in &base.path {
+        commands.spawn((
+            Mesh2d(path_assets.mesh.clone()),
+            MeshMaterial2d(path_assets.material.clone()),
+            Transform::from_translation(layout.grid_to_world(*pos).extend(EDITOR_Z + 2.0)),
+            EditorTile,
+        ));
+    }
+    if base.path.len() > 1 {
+        commands.spawn((
+            Mesh2d(meshes.add(path_line(&base.path, &layout))),
+            MeshMaterial2d(path_assets.material.clone()),
+            Transform::from_xyz(0.0, 0.0, EDITOR_Z + 2.0),
+            EditorTile,
+        ));
+    }
+
+    for (pos, mesh, material) in [
+        (
+            base.start,
+            &path_assets.start_mesh,
+            &path_assets.start_material,
+        ),
+        (base.end, &path_assets.end_mesh, &path_assets.end_material),
+    ] {
+        commands.spawn((
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            Transform::from_translation(layout.grid_to_world(pos).extend(EDITOR_Z + 3.0)),
+            EditorTile,
+        ));
+    }
+}
@@ -1,60 +1,78 @@
+use std::path::PathBuf;
+
 use bevy::prelude::*;
+use editor::*;
+use resources::{HoveredPlacement, MapEditor, Placement};
 use systems::*;
-use tower_defense_plugin::{FreeMap, SimpleMap};
 
 mod components;
+mod editor;
 mod resources;
 mod systems;
 
-pub struct TowerDefenseGui;
-
-impl Plugin for TowerDefenseGui {
-    fn build(&self, app: &mut App) {
-        // Add events
-
-        // Insert resources
-        insert_common_resources(app);
+#[derive(Default)]
+pub struct TowerDefenseGui {
+    /// Map file of the editor, a default path when not set
+    pub editor_map: Option<PathBuf>,
+}
 
-        // Add systems
-        app.add_systems(Startup, setup::<FreeMap>);
-        // Systems at update
-        insert_common_systems(app);
-        app.add_systems(Update, update_path);
+impl TowerDefenseGui {
+    pub fn with_editor_map(mut self, path: PathBuf) -> Self {
+        self.editor_map = Some(path);
+        self
     }
 }
 
-pub struct TowerDefenseGuiSimpleMap;
-
-impl Plugin for TowerDefenseGuiSimpleMap {
+impl Plugin for TowerDefenseGui {
     fn build(&self, app: &mut App) {
         // Add events
 
         // Insert resources
-        insert_common_resources(app);
+        insert_resources(app, self);
 
         // Add systems
-        app.add_systems(Startup, setup::<SimpleMap>);
-        insert_common_systems(app);
+        app.add_systems(Startup, setup);
+        // Systems at update
+        insert_systems(app);
     }
 }
 
-fn insert_common_systems(app: &mut App) {
+fn insert_systems(app: &mut App) {
     app.add_systems(PreUpdate, handle_new_bullets);
     app.add_systems(
         Update,
         (
-            mouse_input,
+            (mouse_input, level_select).run_if(not_editing),
+            update_hovered_placement.after(mouse_input),
+            (placement_preview, ghost_path_preview).after(update_hovered_placement),
+            turret_info,
+            toggle_editor,
+            editor_input.after(toggle_editor).run_if(not(not_editing)),
+            draw_editor.after(editor_input),
             new_turrets,
+            aim_turret_meshes.after(new_turrets),
+            update_path,
+            recenter_map.before(handle_game_loaded),
+            handle_game_loaded,
             handle_new_creep,
             health_bar_system,
             handle_fire_event,
+            handle_bullet_hit_event,
+            handle_beam_event,
             update_fire,
             animate_sprite,
         ),
     );
-    app.add_systems(PostUpdate, despawn_dead_bullets);
 }
 
-fn insert_common_resources(app: &mut App) {
-    app.insert_resource(ClearColor(Color::BLACK));
+fn insert_resources(app: &mut App, gui: &TowerDefenseGui) {
+    let mut editor = MapEditor::default();
+    if let Some(path) = &gui.editor_map {
+        editor.path = path.clone();
+    }
+
+    app.insert_resource(ClearColor(Color::BLACK))
+        .init_resource::<Placement>()
+        .init_resource::<HoveredPlacement>()
+        .insert_resource(editor);
 }
@@ -1,4 +1,10 @@
+use std::path::PathBuf;
+
 use bevy::prelude::*;
+use tower_defense_plugin::components::TurretType;
+use tower_defense_plugin::map::validation::{MapError, derive_waypoints};
+use tower_defense_plugin::resources::GridLayout;
+use tower_defense_plugin::{ActiveMap, MapFile, MapKind};
 
 #[derive(Resource)]
 pub struct TowerAssets {
@@ -7,15 +13,47 @@ pub struct TowerAssets {
     pub bomb_material: Handle<ColorMaterial>,
     pub follower_material: Handle<ColorMaterial>,
     pub slow_material: Handle<ColorMaterial>,
+    pub beam_material: Handle<ColorMaterial>,
+    pub chain_material: Handle<ColorMaterial>,
+    pub mortar_material: Handle<ColorMaterial>,
+    pub aura_material: Handle<ColorMaterial>,
+    /// Drawn on the turrets that turn to aim
+    pub barrel_mesh: Handle<Mesh>,
+    pub barrel_material: Handle<ColorMaterial>,
     pub fire_image: Handle<Image>,
     pub smoke_image: Handle<Image>,
     pub smoke_atlas_layout: Handle<TextureAtlasLayout>,
+    /// Slightly smaller than a cell, used by walls and placement previews
+    pub square_mesh: Handle<Mesh>,
+    pub hex_mesh: Handle<Mesh>,
+    pub wall_material: Handle<ColorMaterial>,
+    pub preview_material: Handle<ColorMaterial>,
+    pub invalid_preview_material: Handle<ColorMaterial>,
+}
+
+/// What the mouse places, shown under the cursor
+#[derive(Resource)]
+pub struct Placement {
+    pub turret_type: TurretType,
+    /// First cell of the line of walls being dragged
+    pub wall_start: Option<IVec2>,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            turret_type: TurretType::Basic,
+            wall_start: None,
+        }
+    }
 }
 
 #[derive(Resource)]
 pub struct BulletAssets {
     pub mesh: Handle<Mesh>,
     pub material: Handle<ColorMaterial>,
+    pub projectile_material: Handle<ColorMaterial>,
+    pub shell_material: Handle<ColorMaterial>,
 }
 
 #[derive(Resource)]
@@ -26,6 +64,12 @@ pub struct PathAssets {
     pub start_material: Handle<ColorMaterial>,
     pub end_mesh: Handle<Mesh>,
     pub end_material: Handle<ColorMaterial>,
+    pub tile_material: Handle<ColorMaterial>,
+    pub road_material: Handle<ColorMaterial>,
+    pub obstacle_material: Handle<ColorMaterial>,
+    /// Lines between the cells of the editor
+    pub grid_material: Handle<ColorMaterial>,
+    pub ghost_material: Handle<ColorMaterial>,
 }
 
 #[derive(Resource)]
@@ -34,3 +78,95 @@ pub struct CreepAssets {
     pub health_bar_back_sprite: Sprite,
     pub health_bar_front_sprite: Sprite,
 }
+
+/// Cells the mouse would place, with the layout of the map in window coordinates
+#[derive(Resource, Default, PartialEq)]
+pub struct HoveredPlacement {
+    pub cells: Vec<IVec2>,
+    pub possible: bool,
+    pub layout: GridLayout,
+}
+
+/// What a click does in the map editor
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditorTool {
+    Road,
+    Obstacle,
+    Buildable,
+    Start,
+    End,
+    Waypoint,
+}
+
+/// Map being edited, the game is paused while the editor is active
+#[derive(Resource)]
+pub struct MapEditor {
+    pub active: bool,
+    pub tool: EditorTool,
+    pub file: MapFile,
+    /// Waypoints of simple maps follow the road, otherwise they are placed by hand
+    pub auto_waypoints: bool,
+    /// Why the map can not be played yet
+    pub error: Option<MapError>,
+    /// Map file written and read by the editor
+    pub path: PathBuf,
+}
+
+impl Default for MapEditor {
+    fn default() -> Self {
+        Self {
+            active: false,
+            tool: EditorTool::Road,
+            file: MapFile {
+                kind: MapKind::Simple,
+                map: default(),
+            },
+            auto_waypoints: true,
+            error: None,
+            path: PathBuf::from("assets/maps/edited.json"),
+        }
+    }
+}
+
+impl MapEditor {
+    /// Edit another map, keeping its waypoints when they were not derived from the road
+    pub fn open(&mut self, file: MapFile) {
+        let map = &file.map;
+        self.auto_waypoints = file.kind != MapKind::Simple
+            || derive_waypoints(&map.cells, map.start, map.end).as_ref() == Ok(&map.path);
+        self.file = file;
+        self.error = None;
+        self.refresh();
+    }
+
+    /// Update the path and the validation after an edit
+    pub fn refresh(&mut self) {
+        let mut error = None;
+        match self.file.kind {
+            MapKind::Simple => {
+                if self.auto_waypoints {
+                    let map = &mut self.file.map;
+                    map.path =
+                        derive_waypoints(&map.cells, map.start, map.end).unwrap_or_else(|err| {
+                            // The road itself is wrong, more precise than the missing waypoints
+                            error = Some(err);
+                            vec![]
+                        });
+                }
+            }
+            MapKind::Free | MapKind::Hex => {
+                // Only shown, free maps compute their path on load
+                self.file.map.path = ActiveMap::from(self.file.clone()).get_path().clone();
+            }
+        }
+
+        let error = error.or_else(|| self.file.validate().err());
+        if error != self.error {
+            match &error {
+                Some(err) => println!("Invalid map: {err}"),
+                None => println!("Map is valid"),
+            }
+            self.error = error;
+        }
+    }
+}
@@ -3,38 +3,93 @@ use crate::resources::*;
 use bevy::asset::RenderAssetUsages;
 use bevy::camera::ScalingMode;
 use bevy::math::vec2;
+use bevy::platform::collections::HashMap;
 use bevy::sprite::Anchor;
 use bevy::window::PrimaryWindow;
 use bevy::{camera::Camera2d, ecs::system::*, prelude::*};
+use tower_defense_plugin::components::BaseStats;
 use tower_defense_plugin::components::Creep;
 use tower_defense_plugin::components::FollowerBullet;
+use tower_defense_plugin::components::MortarShell;
+use tower_defense_plugin::components::Projectile;
+use tower_defense_plugin::components::Turret;
+use tower_defense_plugin::components::TurretAim;
 use tower_defense_plugin::components::TurretType;
 use tower_defense_plugin::events::BasicFireMessage;
+use tower_defense_plugin::events::BulletHitMessage;
+use tower_defense_plugin::events::GameLoadedMessage;
 use tower_defense_plugin::events::MapChangedMessage;
 use tower_defense_plugin::events::NewTurretMessage;
 use tower_defense_plugin::events::PlaceTurretMessage;
+use tower_defense_plugin::events::PlaceWallsMessage;
+use tower_defense_plugin::events::{BeamMessage, BeamState};
+use tower_defense_plugin::resources::{GridLayout, GridShape};
+use tower_defense_plugin::save::SwitchMap;
 use tower_defense_plugin::*;
 use wgpu_types::PrimitiveTopology;
 
-pub fn setup<T>(
+/// Grid layout of the map as drawn around the map anchor
+#[derive(SystemParam)]
+pub struct MapFrame<'w, 's> {
+    layout: Res<'w, GridLayout>,
+    anchor: Query<'w, 's, &'static Transform, With<MapAnchor>>,
+}
+
+impl MapFrame<'_, '_> {
+    /// Layout in window coordinates, once the anchor is spawned
+    pub fn layout(&self) -> Option<GridLayout> {
+        let anchor = self.anchor.single().ok()?;
+        Some(self.layout.translated(anchor.translation.truncate()))
+    }
+}
+
+/// Cell of the map under the mouse cursor
+#[derive(SystemParam)]
+pub struct CursorCell<'w, 's> {
+    q_camera: Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<MainCamera>>,
+    q_windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    map_frame: MapFrame<'w, 's>,
+}
+
+impl CursorCell<'_, '_> {
+    pub fn get(&self) -> Option<IVec2> {
+        let (camera, camera_transform) = self.q_camera.single().ok()?;
+        let cursor = self.q_windows.single().ok()?.cursor_position()?;
+        let position = camera.viewport_to_world_2d(camera_transform, cursor).ok()?;
+        Some(self.layout()?.world_to_grid(position))
+    }
+
+    pub fn layout(&self) -> Option<GridLayout> {
+        self.map_frame.layout()
+    }
+}
+
+pub fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    map: Res<T>,
+    map: Res<ActiveMap>,
+    layout: Res<GridLayout>,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-) where
-    T: Resource + Map,
-{
+) {
     init_assets(
         &mut commands,
         &mut meshes,
         &mut materials,
         &asset_server,
         &mut texture_atlas_layouts,
+        &layout,
     );
 
-    init_path(&mut commands, &mut meshes, &mut materials, map);
+    let anchor = map_anchor(&layout);
+    init_path(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        map,
+        &layout.translated(anchor),
+    );
 
     commands.spawn((
         Camera2d,
@@ -45,25 +100,40 @@ pub fn setup<T>(
             scale: 1.0,
             area: Rect::new(-100.0, -100.0, 100.0, 100.0),
             viewport_origin: Vec2::new(0.5, 0.5),
-            scaling_mode: ScalingMode::AutoMin {
-                min_width: 110.0,
-                min_height: 110.0,
-            },
+            scaling_mode: camera_scaling(&layout),
         }),
     ));
     commands.spawn((
-        Transform::from_xyz(-45.0, -45.0, 10.0),
+        Transform::from_translation(anchor.extend(10.0)),
         Visibility::default(),
         MapAnchor,
     ));
 }
 
+fn grid_size() -> IVec2 {
+    IVec2::new(GRID_WIDTH as i32, GRID_HEIGHT as i32)
+}
+
+/// Position of the map anchor centering the map on the window
+fn map_anchor(layout: &GridLayout) -> Vec2 {
+    -layout.grid_rect(grid_size()).center()
+}
+
+fn camera_scaling(layout: &GridLayout) -> ScalingMode {
+    let grid_rect = layout.grid_rect(grid_size());
+    ScalingMode::AutoMin {
+        min_width: grid_rect.width() * 1.1,
+        min_height: grid_rect.height() * 1.1,
+    }
+}
+
 fn init_assets(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<ColorMaterial>>,
     asset_server: &Res<AssetServer>,
     texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    grid_layout: &GridLayout,
 ) {
     let texture = asset_server.load("smoke05.png");
     let layout = TextureAtlasLayout::from_grid(UVec2::splat(64), 11, 15, None, None);
@@ -75,14 +145,27 @@ fn init_assets(
         bomb_material: materials.add(Color::srgb(1.0, 0.9, 0.8)),
         follower_material: materials.add(Color::srgb(1.0, 0.5, 0.8)),
         slow_material: materials.add(Color::srgb_u8(100, 100, 250)),
+        beam_material: materials.add(Color::srgb(0.4, 1.0, 0.6)),
+        chain_material: materials.add(Color::srgb(0.9, 0.9, 0.2)),
+        mortar_material: materials.add(Color::srgb(0.5, 0.35, 0.2)),
+        aura_material: materials.add(Color::srgb(0.7, 0.4, 1.0)),
+        barrel_mesh: meshes.add(Rectangle::new(5.0, 1.5)),
+        barrel_material: materials.add(Color::srgb(0.2, 0.2, 0.2)),
         fire_image: asset_server.load("shots/shotLarge.png"),
         smoke_image: texture,
         smoke_atlas_layout: texture_atlas_layout,
+        square_mesh: meshes.add(Rectangle::from_length(grid_layout.cell_size * 0.9)),
+        hex_mesh: meshes.add(RegularPolygon::new(grid_layout.hex_radius() * 0.9, 6)),
+        wall_material: materials.add(Color::srgb_u8(120, 110, 100)),
+        preview_material: materials.add(Color::srgba(0.2, 0.9, 0.2, 0.4)),
+        invalid_preview_material: materials.add(Color::srgba(0.9, 0.2, 0.2, 0.4)),
     });
 
     commands.insert_resource(BulletAssets {
         mesh: meshes.add(Circle::new(1.0)),
         material: materials.add(Color::srgb(0.9, 0.4, 0.7)),
+        projectile_material: materials.add(Color::srgb(1.0, 0.9, 0.5)),
+        shell_material: materials.add(Color::srgb(0.3, 0.3, 0.3)),
     });
 
     commands.insert_resource(CreepAssets {
@@ -98,14 +181,13 @@ fn init_assets(
     });
 }
 
-fn init_path<T>(
+fn init_path(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<ColorMaterial>>,
-    map: Res<T>,
-) where
-    T: Resource + Map,
-{
+    map: Res<ActiveMap>,
+    layout: &GridLayout,
+) {
     let path_assets = PathAssets {
         mesh: meshes.add(Rectangle::new(3.0, 3.0)),
         material: materials.add(Color::srgb_u8(218, 165, 35)),
@@ -113,55 +195,68 @@ fn init_path<T>(
         start_material: materials.add(Color::srgb_u8(0, 165, 0)),
         end_mesh: meshes.add(Rectangle::new(8.0, 8.0)),
         end_material: materials.add(Color::srgb_u8(165, 0, 0)),
+        tile_material: materials.add(Color::srgb_u8(85, 20, 10)),
+        road_material: materials.add(Color::srgb_u8(150, 110, 60)),
+        obstacle_material: materials.add(Color::srgb_u8(60, 60, 60)),
+        grid_material: materials.add(Color::BLACK),
+        ghost_material: materials.add(Color::srgba_u8(218, 165, 35, 110)),
     };
 
-    commands.spawn((
-        Mesh2d(meshes.add(Rectangle::new(100.0, 100.0))),
-        MeshMaterial2d(materials.add(Color::srgb_u8(85, 20, 10))),
-        Transform::from_xyz(0.0, 0.0, 0.0),
-    ));
+    draw_tiles(commands, &path_assets, &map, layout, meshes);
 
     draw_path(
         commands,
         &path_assets.mesh,
         &path_assets.material,
         &map,
-        Vec2::new(-45.0, -45.0),
+        layout,
         meshes,
     );
 
-    commands.spawn((
-        Mesh2d(path_assets.start_mesh.clone()),
-        MeshMaterial2d(path_assets.start_material.clone()),
-        Transform::from_xyz(
-            map.get_start().x as f32 * 10.0 - 45.0,
-            map.get_start().y as f32 * 10.0 - 45.0,
-            1.0,
-        ),
-    ));
-
-    commands.spawn((
-        Mesh2d(path_assets.end_mesh.clone()),
-        MeshMaterial2d(path_assets.end_material.clone()),
-        Transform::from_xyz(
-            map.get_end().x as f32 * 10.0 - 45.0,
-            map.get_end().y as f32 * 10.0 - 45.0,
-            1.0,
-        ),
-    ));
+    draw_endpoints(commands, &path_assets, &map, layout);
 
     commands.insert_resource(path_assets);
 }
 
 pub fn mouse_input(
-    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
+    cursor: CursorCell,
     buttons: Res<ButtonInput<MouseButton>>,
-    map_anchor_query: Query<&Transform, With<MapAnchor>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut placement: ResMut<Placement>,
     mut turret_events: MessageWriter<PlaceTurretMessage>,
+    mut wall_events: MessageWriter<PlaceWallsMessage>,
 ) {
+    // Shift + drag places a line of walls when the button is released
+    if buttons.just_released(MouseButton::Left)
+        && let Some(from) = placement.wall_start.take()
+    {
+        if let Some(to) = cursor.get() {
+            println!("placing walls from {:?} to {:?}", from, to);
+            wall_events.write(PlaceWallsMessage { from, to });
+        }
+        return;
+    }
+    if buttons.just_pressed(MouseButton::Left)
+        && keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    {
+        placement.turret_type = TurretType::Wall;
+        placement.wall_start = cursor.get();
+        return;
+    }
+
+    // Control picks the other turret of each button, alt the aura
+    let control = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
     let mut turret_type: Option<TurretType> = None;
-    if buttons.just_pressed(MouseButton::Left) {
+    if buttons.just_pressed(MouseButton::Left) && alt {
+        turret_type = Some(TurretType::Aura);
+    } else if buttons.just_pressed(MouseButton::Left) && control {
+        turret_type = Some(TurretType::Beam);
+    } else if buttons.just_pressed(MouseButton::Right) && control {
+        turret_type = Some(TurretType::Chain);
+    } else if buttons.just_pressed(MouseButton::Middle) && control {
+        turret_type = Some(TurretType::Mortar);
+    } else if buttons.just_pressed(MouseButton::Left) {
         turret_type = Some(TurretType::Basic);
     } else if buttons.just_pressed(MouseButton::Right) {
         turret_type = Some(TurretType::Follower);
@@ -170,23 +265,10 @@ pub fn mouse_input(
     }
 
     if let Some(turret_type) = turret_type
-        && let Ok((camera, camera_transform)) = q_camera.single()
-        && let Ok(window) = q_windows.single()
-        && let Some(cursor) = window.cursor_position()
-        && let Ok(position) = camera.viewport_to_world_2d(camera_transform, cursor)
-        && let Ok(map_anchor) = map_anchor_query.single()
+        && let Some(pos) = cursor.get()
     {
-        let grid_origin = map_anchor.translation.truncate();
-
-        println!(
-            "position: {:?}",
-            position - grid_origin + Vec2::new(2.5, 2.5)
-        );
-
-        let pos = IVec2 {
-            x: ((position.x - grid_origin.x + 5.0) / 10.0) as i32,
-            y: ((position.y - grid_origin.y + 5.0) / 10.0) as i32,
-        };
+        // The preview follows the last turret type placed
+        placement.turret_type = turret_type;
 
         println!("placing turret at {:?}", pos);
 
@@ -197,24 +279,95 @@ pub fn mouse_input(
     }
 }
 
+/// Cells of the next turret or line of walls under the cursor
+pub fn update_hovered_placement(
+    cursor: CursorCell,
+    placement: Res<Placement>,
+    map: Res<ActiveMap>,
+    mut hovered: ResMut<HoveredPlacement>,
+) {
+    let next = match cursor.get().zip(cursor.layout()) {
+        Some((pos, layout)) => {
+            let cells = match placement.wall_start {
+                Some(start) => layout.line(start, pos),
+                None => placement.turret_type.cells(pos),
+            };
+            HoveredPlacement {
+                possible: map.is_turret_possible(&cells),
+                cells,
+                layout,
+            }
+        }
+        None => HoveredPlacement::default(),
+    };
+    // A changed map changes the previews even when the cursor stays still
+    if map.is_changed() {
+        *hovered = next;
+    } else {
+        hovered.set_if_neq(next);
+    }
+}
+
+/// Hovered cells, red when they can not be placed
+pub fn placement_preview(
+    mut commands: Commands,
+    hovered: Res<HoveredPlacement>,
+    tower_assets: Res<TowerAssets>,
+    q_preview: Query<Entity, With<PreviewCell>>,
+) {
+    if !hovered.is_changed() {
+        return;
+    }
+    q_preview.iter().for_each(|e| commands.entity(e).despawn());
+
+    let layout = &hovered.layout;
+    let mesh = match layout.shape {
+        GridShape::Square => &tower_assets.square_mesh,
+        GridShape::Hex => &tower_assets.hex_mesh,
+    };
+    let material = if hovered.possible {
+        &tower_assets.preview_material
+    } else {
+        &tower_assets.invalid_preview_material
+    };
+    for cell in &hovered.cells {
+        commands.spawn((
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            Transform::from_translation(layout.grid_to_world(*cell).extend(60.0)),
+            PreviewCell,
+        ));
+    }
+}
+
+/// Turret meshes grow with the footprint of their turret
+fn footprint_scale(cells: &[IVec2]) -> f32 {
+    let min = cells.iter().copied().reduce(IVec2::min).unwrap_or_default();
+    let max = cells.iter().copied().reduce(IVec2::max).unwrap_or_default();
+    (max - min + IVec2::ONE).max_element() as f32
+}
+
 pub fn new_turrets(
     mut commands: Commands,
     tower_assets: Res<TowerAssets>,
     mut events: MessageReader<NewTurretMessage>,
-    map_anchor_query: Query<&Transform, With<MapAnchor>>,
+    map_frame: MapFrame,
 ) {
-    if let Ok(map_anchor) = map_anchor_query.single() {
-        let grid_origin = map_anchor.translation.truncate();
-
+    if let Some(layout) = map_frame.layout() {
         for event in events.read() {
+            let cells = event.turret_type.cells(event.position);
+            // Walls fill their cell, turrets are round
+            let mesh = match (event.turret_type, layout.shape) {
+                (TurretType::Wall, GridShape::Square) => &tower_assets.square_mesh,
+                (TurretType::Wall, GridShape::Hex) => &tower_assets.hex_mesh,
+                _ => &tower_assets.mesh,
+            };
             let turret_id = commands
                 .spawn((
-                    Mesh2d(tower_assets.mesh.clone()),
-                    Transform::from_xyz(
-                        (event.position.x as f32) * 10.0 + grid_origin.x,
-                        (event.position.y as f32) * 10.0 + grid_origin.y,
-                        50.0,
-                    ),
+                    Mesh2d(mesh.clone()),
+                    Transform::from_translation(layout.cells_center(&cells).extend(50.0))
+                        .with_scale(Vec3::splat(footprint_scale(&cells))),
+                    TurretMesh,
                 ))
                 .id();
             match event.turret_type {
@@ -238,85 +391,391 @@ pub fn new_turrets(
                         .entity(turret_id)
                         .insert(MeshMaterial2d(tower_assets.slow_material.clone()));
                 }
+                TurretType::Beam => {
+                    commands
+                        .entity(turret_id)
+                        .insert(MeshMaterial2d(tower_assets.beam_material.clone()));
+                }
+                TurretType::Chain => {
+                    commands
+                        .entity(turret_id)
+                        .insert(MeshMaterial2d(tower_assets.chain_material.clone()));
+                }
+                TurretType::Mortar => {
+                    commands
+                        .entity(turret_id)
+                        .insert(MeshMaterial2d(tower_assets.mortar_material.clone()));
+                }
+                TurretType::Aura => {
+                    commands
+                        .entity(turret_id)
+                        .insert(MeshMaterial2d(tower_assets.aura_material.clone()));
+                }
+                TurretType::Wall => {
+                    commands
+                        .entity(turret_id)
+                        .insert(MeshMaterial2d(tower_assets.wall_material.clone()));
+                }
+            }
+            if event.turret_type.turn_rate().is_some() {
+                commands.entity(turret_id).insert((
+                    AimedTurret {
+                        position: event.position,
+                    },
+                    children![(
+                        Mesh2d(tower_assets.barrel_mesh.clone()),
+                        MeshMaterial2d(tower_assets.barrel_material.clone()),
+                        Transform::from_xyz(3.0, 0.0, 1.0),
+                    )],
+                ));
             }
         }
     }
 }
 
+/// Rotate the turret meshes along the facing of their turret
+pub fn aim_turret_meshes(
+    turrets: Query<(&Turret, &TurretAim), Changed<TurretAim>>,
+    mut meshes: Query<(&AimedTurret, &mut Transform)>,
+) {
+    let angles: HashMap<IVec2, f32> = turrets
+        .iter()
+        .map(|(turret, aim)| (turret.position, aim.angle))
+        .collect();
+    if angles.is_empty() {
+        return;
+    }
+    for (aimed, mut transform) in &mut meshes {
+        if let Some(angle) = angles.get(&aimed.position) {
+            transform.rotation = Quat::from_rotation_z(*angle);
+        }
+    }
+}
+
 pub fn update_path(
     mut commands: Commands,
     q_path: Query<Entity, With<Path>>,
     path_assets: Res<PathAssets>,
-    map: Res<FreeMap>,
-    map_anchor_query: Query<&Transform, With<MapAnchor>>,
+    map: Res<ActiveMap>,
+    map_frame: MapFrame,
     mut events: MessageReader<MapChangedMessage>,
     mut meshes: ResMut<Assets<Mesh>>,
 ) {
     if events.read().len() != 0
-        && let Ok(map_anchor) = map_anchor_query.single()
+        && let Some(layout) = map_frame.layout()
     {
-        let grid_origin = map_anchor.translation.truncate();
         q_path.iter().for_each(|e| commands.entity(e).despawn());
-        draw_path::<FreeMap>(
+        draw_path(
+            &mut commands,
+            &path_assets.mesh,
+            &path_assets.material,
+            &map,
+            &layout,
+            &mut meshes,
+        );
+    }
+}
+
+type StaleVisuals<'w, 's> = Query<
+    'w,
+    's,
+    Entity,
+    Or<(
+        With<Path>,
+        With<Endpoint>,
+        With<TurretMesh>,
+        With<MapTile>,
+        With<BeamLine>,
+    )>,
+>;
+
+/// The loaded map may have another shape, center it again
+pub fn recenter_map(
+    mut events: MessageReader<GameLoadedMessage>,
+    layout: Res<GridLayout>,
+    mut q_anchor: Query<&mut Transform, With<MapAnchor>>,
+    mut q_projection: Query<&mut Projection, With<MainCamera>>,
+) {
+    if events.read().len() != 0 {
+        if let Ok(mut anchor) = q_anchor.single_mut() {
+            anchor.translation = map_anchor(&layout).extend(anchor.translation.z);
+        }
+        if let Ok(mut projection) = q_projection.single_mut()
+            && let Projection::Orthographic(orthographic) = &mut *projection
+        {
+            orthographic.scaling_mode = camera_scaling(&layout);
+        }
+    }
+}
+
+pub fn handle_game_loaded(
+    mut commands: Commands,
+    q_stale: StaleVisuals,
+    path_assets: Res<PathAssets>,
+    map: Res<ActiveMap>,
+    map_frame: MapFrame,
+    mut events: MessageReader<GameLoadedMessage>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if events.read().len() != 0
+        && let Some(layout) = map_frame.layout()
+    {
+        // Turrets of the loaded game are announced again through NewTurretMessage
+        q_stale.iter().for_each(|e| commands.entity(e).despawn());
+        draw_tiles(&mut commands, &path_assets, &map, &layout, &mut meshes);
+        draw_path(
             &mut commands,
             &path_assets.mesh,
             &path_assets.material,
             &map,
-            grid_origin,
+            &layout,
             &mut meshes,
         );
+        draw_endpoints(&mut commands, &path_assets, &map, &layout);
+    }
+}
+
+pub fn level_select(mut commands: Commands, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::Digit1) {
+        commands.queue(SwitchMap(ActiveMap(Box::new(SimpleMap::default()))));
+    } else if keys.just_pressed(KeyCode::Digit2) {
+        commands.queue(SwitchMap(ActiveMap(Box::new(FreeMap::default()))));
+    } else if keys.just_pressed(KeyCode::Digit3) {
+        commands.queue(SwitchMap(ActiveMap(Box::new(HexMap::default()))));
+    }
+}
+
+fn draw_tiles(
+    commands: &mut Commands,
+    path_assets: &PathAssets,
+    map: &ActiveMap,
+    layout: &GridLayout,
+    meshes: &mut ResMut<Assets<Mesh>>,
+) {
+    // Slightly smaller hexagons leave the grid lines visible
+    let cell_mesh = match layout.shape {
+        GridShape::Square => meshes.add(Rectangle::from_length(layout.cell_size)),
+        GridShape::Hex => meshes.add(RegularPolygon::new(layout.hex_radius() * 0.95, 6)),
+    };
+    match layout.shape {
+        GridShape::Square => {
+            let grid_rect = layout.grid_rect(grid_size());
+            commands.spawn((
+                Mesh2d(meshes.add(Rectangle::from_size(grid_rect.size()))),
+                MeshMaterial2d(path_assets.tile_material.clone()),
+                Transform::from_translation(grid_rect.center().extend(0.0)),
+                MapTile,
+            ));
+        }
+        GridShape::Hex => {
+            for x in 0..GRID_WIDTH as i32 {
+                for y in 0..GRID_HEIGHT as i32 {
+                    commands.spawn((
+                        Mesh2d(cell_mesh.clone()),
+                        MeshMaterial2d(path_assets.tile_material.clone()),
+                        Transform::from_translation(
+                            layout.grid_to_world(IVec2::new(x, y)).extend(0.0),
+                        ),
+                        MapTile,
+                    ));
+                }
+            }
+        }
+    }
+
+    let cells = map.snapshot().map.cells;
+    for (x, column) in cells.iter().enumerate() {
+        for (y, cell) in column.iter().enumerate() {
+            if *cell == OBSTACLE {
+                commands.spawn((
+                    Mesh2d(cell_mesh.clone()),
+                    MeshMaterial2d(path_assets.obstacle_material.clone()),
+                    Transform::from_translation(
+                        layout
+                            .grid_to_world(IVec2::new(x as i32, y as i32))
+                            .extend(0.05),
+                    ),
+                    MapTile,
+                ));
+            }
+        }
     }
 }
 
-pub fn draw_path<T>(
+fn draw_endpoints(
+    commands: &mut Commands,
+    path_assets: &PathAssets,
+    map: &ActiveMap,
+    layout: &GridLayout,
+) {
+    commands.spawn((
+        Mesh2d(path_assets.start_mesh.clone()),
+        MeshMaterial2d(path_assets.start_material.clone()),
+        Transform::from_translation(layout.grid_to_world(map.get_start()).extend(1.0)),
+        Endpoint {},
+    ));
+
+    commands.spawn((
+        Mesh2d(path_assets.end_mesh.clone()),
+        MeshMaterial2d(path_assets.end_material.clone()),
+        Transform::from_translation(layout.grid_to_world(map.get_end()).extend(1.0)),
+        Endpoint {},
+    ));
+}
+
+pub fn draw_path(
     commands: &mut Commands,
     mesh: &Handle<Mesh>,
     material: &Handle<ColorMaterial>,
-    map: &Res<T>,
-    grid_origin: Vec2,
+    map: &ActiveMap,
+    layout: &GridLayout,
     meshes: &mut ResMut<Assets<Mesh>>,
-) where
-    T: Resource + Map,
-{
+) {
     for pos in &map.get_path()[1..map.get_path().len() - 1] {
         commands.spawn((
             Mesh2d(mesh.clone()),
             MeshMaterial2d(material.clone()),
-            Transform::from_xyz(
-                (pos.x as f32) * 10.0 + grid_origin.x,
-                (pos.y as f32) * 10.0 + grid_origin.y,
-                1.0,
-            ),
+            Transform::from_translation(layout.grid_to_world(*pos).extend(1.0)),
             Path {},
         ));
     }
-    let points: Vec<Vec2> = map
-        .get_path()
-        .iter()
-        .map(|pos| {
-            Vec2::new(
-                (pos.x as f32) * 10.0 + grid_origin.x,
-                (pos.y as f32) * 10.0 + grid_origin.y,
-            )
-        })
-        .collect();
+    commands.spawn((
+        Mesh2d(meshes.add(path_line(map.get_path(), layout))),
+        MeshMaterial2d(material.clone()),
+        Transform::from_xyz(0.0, 0.0, 1.0),
+        Path {},
+    ));
+}
 
+pub fn path_line(path: &[IVec2], layout: &GridLayout) -> Mesh {
     let mut mesh = Mesh::new(PrimitiveTopology::LineStrip, RenderAssetUsages::all());
     let mut vertices = Vec::new();
-    for point in &points {
+    for pos in path {
+        let point = layout.grid_to_world(*pos);
         vertices.push([point.x, point.y, 0.0]);
     }
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-    let mesh_handle = meshes.add(mesh);
+    mesh
+}
+
+/// World length of a path, in cells
+fn path_length(path: &[IVec2], layout: &GridLayout) -> f32 {
+    let length: f32 = path
+        .windows(2)
+        .map(|step| {
+            layout
+                .grid_to_world(step[0])
+                .distance(layout.grid_to_world(step[1]))
+        })
+        .sum();
+    length / layout.cell_size
+}
+
+/// Path the creeps would take with the hovered placement, and how much longer it is
+pub fn ghost_path_preview(
+    mut commands: Commands,
+    hovered: Res<HoveredPlacement>,
+    map: Res<ActiveMap>,
+    path_assets: Res<PathAssets>,
+    q_ghost: Query<Entity, With<GhostPath>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !hovered.is_changed() {
+        return;
+    }
+    q_ghost.iter().for_each(|e| commands.entity(e).despawn());
 
+    let Some(dynamic) = map.as_dynamic() else {
+        return;
+    };
+    if !hovered.possible
+        || !hovered
+            .cells
+            .iter()
+            .any(|cell| map.get_path().contains(cell))
+    {
+        return;
+    }
+    let Some((ghost, _)) = dynamic.compute_path_blocked(&map.get_start(), &hovered.cells) else {
+        return;
+    };
+
+    let layout = &hovered.layout;
     commands.spawn((
-        Mesh2d(mesh_handle.clone()),
-        MeshMaterial2d(material.clone()),
-        Transform::from_xyz(0.0, 0.0, 1.0),
-        Path {},
+        Mesh2d(meshes.add(path_line(&ghost, layout))),
+        MeshMaterial2d(path_assets.ghost_material.clone()),
+        Transform::from_xyz(0.0, 0.0, 2.0),
+        GhostPath,
+    ));
+
+    let delta = path_length(&ghost, layout) - path_length(map.get_path(), layout);
+    let label_position = layout.cells_center(&hovered.cells) + Vec2::Y * layout.cell_size;
+    commands.spawn((
+        Text2d::new(format!("{delta:+.1}")),
+        TextFont::from_font_size(24.0),
+        TextColor(Color::WHITE),
+        Transform::from_translation(label_position.extend(70.0)).with_scale(Vec3::splat(0.25)),
+        GhostPath,
     ));
 }
 
+/// Effective stats of the hovered turret, with the share added by auras
+pub fn turret_info(
+    mut commands: Commands,
+    cursor: CursorCell,
+    turrets: Query<(&Turret, &BaseStats)>,
+    q_info: Query<Entity, With<TurretInfo>>,
+    mut shown: Local<Option<String>>,
+) {
+    let hovered = cursor
+        .get()
+        .zip(cursor.layout())
+        .and_then(|(cell, layout)| {
+            let (turret, base) = turrets
+                .iter()
+                .find(|(turret, _)| turret.turret_type.cells(turret.position).contains(&cell))?;
+            Some((turret, base, layout))
+        });
+    let text = hovered.map(|(turret, base, _)| {
+        format!(
+            "{:?}\n{}\n{}\n{}",
+            turret.turret_type,
+            stat_line("damage", turret.damage, base.damage),
+            stat_line("range", turret.range, base.range),
+            // A shorter reload is a bonus
+            stat_line("reload", turret.reload_time, base.reload_time),
+        )
+    });
+    if *shown == text {
+        return;
+    }
+    q_info.iter().for_each(|e| commands.entity(e).despawn());
+
+    if let (Some((turret, _, layout)), Some(text)) = (hovered, &text) {
+        let cells = turret.turret_type.cells(turret.position);
+        let label_position = layout.cells_center(&cells) + Vec2::Y * layout.cell_size * 1.5;
+        commands.spawn((
+            Text2d::new(text.clone()),
+            TextFont::from_font_size(24.0),
+            TextColor(Color::WHITE),
+            Transform::from_translation(label_position.extend(70.0)).with_scale(Vec3::splat(0.25)),
+            TurretInfo,
+        ));
+    }
+    *shown = text;
+}
+
+fn stat_line(name: &str, effective: f32, base: f32) -> String {
+    if base == 0.0 || (effective - base).abs() < f32::EPSILON {
+        format!("{name} {effective:.1}")
+    } else {
+        format!(
+            "{name} {effective:.1} ({:+.0}%)",
+            (effective / base - 1.0) * 100.0
+        )
+    }
+}
+
 pub fn handle_new_creep(
     mut commands: Commands,
     mut query: Query<(Entity, &Creep), Added<Creep>>,
@@ -351,31 +810,37 @@ pub fn handle_new_creep(
     }
 }
 
+type NewBullets<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, Has<Projectile>, Has<MortarShell>),
+    Or<(Added<FollowerBullet>, Added<Projectile>, Added<MortarShell>)>,
+>;
+
 pub fn handle_new_bullets(
     mut commands: Commands,
-    mut query: Query<Entity, Added<FollowerBullet>>,
+    query: NewBullets,
     map_anchor_query: Query<(Entity, &MapAnchor)>,
     bullet_assets: Res<BulletAssets>,
 ) {
     if let Ok((anchor, _)) = map_anchor_query.single() {
-        for entity in &mut query {
+        for (entity, is_projectile, is_shell) in &query {
+            let material = if is_projectile {
+                &bullet_assets.projectile_material
+            } else if is_shell {
+                &bullet_assets.shell_material
+            } else {
+                &bullet_assets.material
+            };
             commands.entity(entity).insert_if_new((
                 Mesh2d(bullet_assets.mesh.clone()),
-                MeshMaterial2d(bullet_assets.material.clone()),
+                MeshMaterial2d(material.clone()),
             ));
             commands.entity(entity).insert(ChildOf(anchor));
         }
     }
 }
 
-pub fn despawn_dead_bullets(mut commands: Commands, query: Query<(Entity, &FollowerBullet)>) {
-    for (entity, bullet) in &query {
-        if commands.get_entity(bullet.target).is_err() {
-            commands.entity(entity).despawn();
-        }
-    }
-}
-
 pub fn health_bar_system(
     q_creep: Query<(&Creep, &Children), Changed<Creep>>,
     mut q_health_bar: Query<&mut Sprite, With<HealthBar>>,
@@ -397,11 +862,15 @@ pub fn handle_fire_event(
 ) {
     if let Ok((anchor, _)) = map_anchor_query.single() {
         for event in fire_events.read() {
-            let direction = (event.target - grid_to_world(event.origin)).normalize();
+            // Fire and smoke are children of the anchor, in game coordinates
+            let origin = event.origin;
+            let direction = (event.target - origin).normalize();
             let angle = direction.y.atan2(direction.x);
 
-            let fire = create_fire_entity(&mut commands, &tower_assets, event.origin, angle);
-            let smoke = create_smoke_entity(&mut commands, &tower_assets, event.target);
+            let fire =
+                create_fire_entity(&mut commands, &tower_assets, origin, angle, event.is_crit);
+            let smoke =
+                create_smoke_entity(&mut commands, &tower_assets, event.target, event.is_crit);
 
             commands.entity(anchor).add_child(fire);
             commands.entity(anchor).add_child(smoke);
@@ -409,23 +878,102 @@ pub fn handle_fire_event(
     }
 }
 
+/// Projectiles and follower bullets leave a smoke where they hit, larger on critical hits
+pub fn handle_bullet_hit_event(
+    mut commands: Commands,
+    mut hit_events: MessageReader<BulletHitMessage>,
+    tower_assets: Res<TowerAssets>,
+    map_anchor_query: Query<Entity, With<MapAnchor>>,
+) {
+    if let Ok(anchor) = map_anchor_query.single() {
+        for event in hit_events.read() {
+            let smoke =
+                create_smoke_entity(&mut commands, &tower_assets, event.target, event.is_crit);
+            commands.entity(anchor).add_child(smoke);
+        }
+    }
+}
+
+/// Keeps one line per firing beam turret, thicker as its damage ramps up
+pub fn handle_beam_event(
+    mut commands: Commands,
+    mut beam_events: MessageReader<BeamMessage>,
+    mut q_beams: Query<(Entity, &BeamLine, &mut Sprite, &mut Transform)>,
+    map_anchor_query: Query<Entity, With<MapAnchor>>,
+) {
+    // Several ticks may run in one frame, only the last state of each beam is drawn
+    let mut latest = bevy::platform::collections::HashMap::new();
+    for event in beam_events.read() {
+        latest.insert(event.turret, event);
+    }
+
+    for (entity, line, mut sprite, mut transform) in &mut q_beams {
+        if let Some(event) = latest.remove(&line.turret) {
+            if event.state == BeamState::Stop {
+                commands.entity(entity).despawn();
+            } else {
+                (sprite.custom_size, *transform) = beam_geometry(event);
+            }
+        }
+    }
+
+    let Ok(anchor) = map_anchor_query.single() else {
+        return;
+    };
+    for event in latest.into_values() {
+        if event.state != BeamState::Stop {
+            let (size, transform) = beam_geometry(event);
+            let mut sprite = Sprite::from_color(Color::srgb(0.6, 1.0, 0.7), Vec2::ONE);
+            sprite.custom_size = size;
+            commands.spawn((
+                BeamLine {
+                    turret: event.turret,
+                },
+                sprite,
+                Anchor::CENTER_LEFT,
+                transform,
+                ChildOf(anchor),
+            ));
+        }
+    }
+}
+
+fn beam_geometry(event: &BeamMessage) -> (Option<Vec2>, Transform) {
+    let delta = event.target - event.origin;
+    let size = Vec2::new(delta.length(), 0.4 * event.ramp);
+    let transform = Transform {
+        translation: event.origin.extend(90.0),
+        rotation: Quat::from_rotation_z(delta.y.atan2(delta.x)),
+        ..Default::default()
+    };
+    (Some(size), transform)
+}
+
 fn create_fire_entity(
     commands: &mut Commands,
     tower_assets: &Res<TowerAssets>,
-    origin: IVec2,
+    origin: Vec2,
     angle: f32,
+    is_crit: bool,
 ) -> Entity {
+    // Critical hits get a larger, red flash
+    let (size, color) = if is_crit {
+        (Vec2::new(6.0, 2.5), Color::srgb(1.0, 0.3, 0.2))
+    } else {
+        (Vec2::new(3.5, 1.5), Color::WHITE)
+    };
     commands
         .spawn(FireBundle {
             fire: Fire { time_left: 0.1 },
             sprite: Sprite {
                 image: tower_assets.fire_image.clone(),
-                custom_size: Some(Vec2::new(3.5, 1.5)),
+                custom_size: Some(size),
+                color,
                 ..Default::default()
             },
             anchor: Anchor::CENTER_LEFT,
             transform: Transform {
-                translation: grid_to_world(origin).extend(100.0),
+                translation: origin.extend(100.0),
                 rotation: Quat::from_rotation_z(angle),
                 ..Default::default()
             },
@@ -437,7 +985,9 @@ fn create_smoke_entity(
     commands: &mut Commands,
     tower_assets: &Res<TowerAssets>,
     target: Vec2,
+    is_crit: bool,
 ) -> Entity {
+    let size = if is_crit { 12.0 } else { 8.0 };
     let animation_indices = AnimationIndices {
         first: 66,
         last: 66 + 10,
@@ -446,7 +996,7 @@ fn create_smoke_entity(
     commands
         .spawn((
             Sprite {
-                custom_size: Some(Vec2::new(8.0, 8.0)),
+                custom_size: Some(Vec2::splat(size)),
                 ..Sprite::from_atlas_image(
                     tower_assets.smoke_image.clone(),
                     TextureAtlas {
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use std::hint::black_box;
+use tower_defense_plugin::resources::CreepSpatialIndex;
+
+const TURRETS: usize = 100;
+const RANGE: f32 = 25.0;
+/// Creeps spread over a map of 20 by 20 cells of 10 units
+const MAP_SIZE: f32 = 200.0;
+
+fn random_positions(rng: &mut SmallRng, n: usize) -> Vec<Vec2> {
+    (0..n)
+        .map(|_| {
+            Vec2::new(
+                rng.random_range(0.0..MAP_SIZE),
+                rng.random_range(0.0..MAP_SIZE),
+            )
+        })
+        .collect()
+}
+
+/// Every turret finds the creeps in its range, by scanning all of them or through the index
+fn range_queries(c: &mut Criterion) {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut world = World::new();
+    let turrets = random_positions(&mut rng, TURRETS);
+
+    let mut group = c.benchmark_group("range_queries");
+    for n in [100, 1_000, 10_000] {
+        let creeps: Vec<(Entity, Vec2)> = random_positions(&mut rng, n)
+            .into_iter()
+            .map(|position| (world.spawn_empty().id(), position))
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("scan", n), &creeps, |b, creeps| {
+            b.iter(|| {
+                turrets
+                    .iter()
+                    .map(|turret| {
+                        creeps
+                            .iter()
+                            .filter(|(_, position)| turret.distance(*position) <= RANGE)
+                            .count()
+                    })
+                    .sum::<usize>()
+            })
+        });
+
+        // The index is rebuilt every tick, its cost is part of the measure
+        let mut index = CreepSpatialIndex::default();
+        group.bench_with_input(BenchmarkId::new("index", n), &creeps, |b, creeps| {
+            b.iter(|| {
+                index.clear();
+                for (entity, position) in creeps {
+                    index.insert(*entity, *position);
+                }
+                turrets
+                    .iter()
+                    .map(|turret| black_box(&index).within(*turret, RANGE).count())
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, range_queries);
+criterion_main!(benches);
@@ -1,9 +1,12 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Creep {
     pub health: f32,
     pub max_health: f32,
+    pub bounty: i32,
+    pub wave: usize,
 }
 
 #[derive(Bundle)]
@@ -13,21 +16,69 @@ pub struct CreepBundle {
     pub creep: Creep,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct MovingEntity {
     pub waypoints: Vec<Vec2>,
     pub speed: f32,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum TurretType {
     Basic,
     Bomb,
     Follower,
     Slow,
+    /// Keeps a beam on one creep, dealing more damage the longer it stays on it
+    Beam,
+    /// Its hit jumps from its target to the creeps around
+    Chain,
+    /// Shells the ground where creeps will be, can not hit creeps too close to it
+    Mortar,
+    /// Does not shoot, makes the turrets around it stronger
+    Aura,
+    /// Cheap blocker which does not shoot, used to shape the maze
+    Wall,
 }
 
-#[derive(Component)]
+impl TurretType {
+    /// Cells occupied by the turret, relative to its position
+    pub fn footprint(&self) -> &'static [IVec2] {
+        match self {
+            TurretType::Bomb => &[IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE],
+            TurretType::Basic
+            | TurretType::Follower
+            | TurretType::Slow
+            | TurretType::Beam
+            | TurretType::Chain
+            | TurretType::Mortar
+            | TurretType::Aura
+            | TurretType::Wall => &[IVec2::ZERO],
+        }
+    }
+
+    /// Radians per second turned while aiming, `None` for turrets firing in every direction
+    pub fn turn_rate(&self) -> Option<f32> {
+        match self {
+            TurretType::Basic => Some(4.0),
+            TurretType::Follower | TurretType::Chain => Some(3.0),
+            TurretType::Mortar => Some(1.5),
+            TurretType::Slow
+            | TurretType::Bomb
+            | TurretType::Beam
+            | TurretType::Aura
+            | TurretType::Wall => None,
+        }
+    }
+
+    pub fn cells(&self, position: IVec2) -> Vec<IVec2> {
+        self.footprint()
+            .iter()
+            .map(|offset| position + offset)
+            .collect()
+    }
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Turret {
     pub turret_type: TurretType,
     pub position: IVec2,
@@ -38,6 +89,70 @@ pub struct Turret {
     pub last_fired: f32,
 }
 
+/// Stats of a turret before the bonuses of auras, `Turret` holds the effective ones
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct BaseStats {
+    pub range: f32,
+    pub damage: f32,
+    pub reload_time: f32,
+}
+
+impl From<&Turret> for BaseStats {
+    fn from(turret: &Turret) -> Self {
+        BaseStats {
+            range: turret.range,
+            damage: turret.damage,
+            reload_time: turret.reload_time,
+        }
+    }
+}
+
+/// Facing of a turret, which only fires once `aligned` with its target
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct TurretAim {
+    pub angle: f32,
+    pub turn_rate: f32,
+    pub aligned: bool,
+}
+
+impl TurretAim {
+    pub fn new(turn_rate: f32) -> Self {
+        TurretAim {
+            angle: 0.0,
+            turn_rate,
+            aligned: false,
+        }
+    }
+}
+
+/// Turrets with a variance deal `damage` give or take `spread` of it, and sometimes critical hits
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct DamageVariance {
+    pub spread: f32,
+    pub crit_chance: f32,
+    pub crit_multiplier: f32,
+}
+
+/// Bonuses given to the turrets within `cells` cells, added up when auras overlap
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct AuraTurret {
+    pub cells: f32,
+    pub range: f32,
+    pub damage: f32,
+    pub fire_rate: f32,
+}
+
+impl Default for AuraTurret {
+    fn default() -> Self {
+        AuraTurret {
+            cells: 2.0,
+            range: 0.1,
+            damage: 0.2,
+            fire_rate: 0.15,
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct BasicTurret {}
 
@@ -47,27 +162,102 @@ pub struct BombTurret {}
 #[derive(Component)]
 pub struct SlowTurret {}
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct BulletThrower {
     pub speed: f32,
 }
 
+/// Each hit jumps to up to `bounces` other creeps within `bounce_radius` of the last one hit
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct ChainTurret {
+    pub bounces: usize,
+    pub bounce_radius: f32,
+    /// Share of the damage lost at each bounce
+    pub decay: f32,
+}
+
+impl Default for ChainTurret {
+    fn default() -> Self {
+        ChainTurret {
+            bounces: 3,
+            bounce_radius: 20.0,
+            decay: 0.3,
+        }
+    }
+}
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct MortarTurret {
+    /// Creeps closer than this are out of reach
+    pub min_range: f32,
+    pub splash_radius: f32,
+    pub flight_time: f32,
+}
+
+impl Default for MortarTurret {
+    fn default() -> Self {
+        MortarTurret {
+            min_range: 25.0,
+            splash_radius: 12.0,
+            flight_time: 1.5,
+        }
+    }
+}
+
+/// Flies to a point on the ground and damages every creep around it on landing
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct MortarShell {
+    pub target: Vec2,
+    pub velocity: Vec2,
+    pub time_to_impact: f32,
+    pub radius: f32,
+    pub damage: f32,
+}
+
+/// Basic turrets with a launcher fire projectiles instead of hitting instantly
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct ProjectileLauncher {
+    pub speed: f32,
+}
+
+/// Creep the beam is locked onto, and for how long
+#[derive(Component, Clone, Default)]
+pub struct BeamTurret {
+    pub target: Option<Entity>,
+    pub time_on_target: f32,
+}
+
+/// Flies in a straight line and only damages its target, misses once its time is over
+#[derive(Component)]
+pub struct Projectile {
+    pub velocity: Vec2,
+    pub target: Entity,
+    pub damage: f32,
+    pub is_crit: bool,
+    pub time_to_live: f32,
+}
+
+/// Chases its target, then the next creep picked by `strategy` within `range`, until its time is over
 #[derive(Component)]
 pub struct FollowerBullet {
     pub direction: Vec2,
     pub target: Entity,
     pub damage: f32,
+    pub is_crit: bool,
     pub speed: f32,
     pub angular_velocity: f32,
+    pub strategy: Strategy,
+    pub range: f32,
+    pub time_to_live: f32,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct SlowDown {
     pub time_to_live: f32,
     pub strength: f32,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
 pub enum Strategy {
     Weakest,
     Strongest,
@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::MapFile;
+use crate::resources::{GameData, GridLayout};
+
+/// Multipliers applied to every spawned creep
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Difficulty {
+    pub creep_health: f32,
+    pub creep_speed: f32,
+    pub creep_bounty: f32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self {
+            creep_health: 1.0,
+            creep_speed: 1.0,
+            creep_bounty: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub enum MapSource {
+    /// Keep the map inserted by the embedder, or the default fixed path map
+    #[default]
+    Default,
+    File(PathBuf),
+    Loaded(MapFile),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnabledSystems {
+    pub spawning: bool,
+    pub movement: bool,
+    pub turrets: bool,
+    pub placement: bool,
+}
+
+impl Default for EnabledSystems {
+    fn default() -> Self {
+        Self {
+            spawning: true,
+            movement: true,
+            turrets: true,
+            placement: true,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub starting_gold: i32,
+    pub starting_lives: i32,
+    pub difficulty: Difficulty,
+    /// Seed of the creep and damage random generators, random when `None`
+    pub seed: Option<u64>,
+    pub map: MapSource,
+    /// Run the simulation at a fixed number of updates per second instead of once per frame
+    pub tick_rate: Option<f64>,
+    pub systems: EnabledSystems,
+    pub layout: GridLayout,
+    /// Creeps walk in straight lines between the turns of their path instead of cell by cell
+    pub smooth_paths: bool,
+    /// Basic turrets launch projectiles flying at this speed instead of hitting instantly
+    pub basic_projectile_speed: Option<f32>,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        let game_data = GameData::default();
+        Self {
+            starting_gold: game_data.gold,
+            starting_lives: game_data.lives,
+            difficulty: Difficulty::default(),
+            seed: None,
+            map: MapSource::Default,
+            tick_rate: None,
+            systems: EnabledSystems::default(),
+            layout: GridLayout::default(),
+            smooth_paths: false,
+            basic_projectile_speed: None,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn starting_game_data(&self) -> GameData {
+        GameData {
+            score: 0,
+            lives: self.starting_lives,
+            gold: self.starting_gold,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::TowerDefensePlugin;
+    use crate::components::Creep;
+    use bevy::time::TimeUpdateStrategy;
+
+    fn count_creeps(plugin: TowerDefensePlugin) -> (usize, GameData) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(plugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                0.1,
+            )));
+        for _ in 0..50 {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        let creeps = world.query::<&Creep>().iter(world).count();
+        (creeps, world.resource::<GameData>().clone())
+    }
+
+    #[test]
+    fn configure_plugin() {
+        let (creeps, game_data) = count_creeps(
+            TowerDefensePlugin::default()
+                .with_starting_gold(42)
+                .with_starting_lives(3)
+                .with_seed(7),
+        );
+        assert!(creeps > 0);
+        assert_eq!(game_data.gold, 42);
+        assert_eq!(game_data.lives, 3);
+
+        let (creeps, _) =
+            count_creeps(TowerDefensePlugin::default().with_systems(EnabledSystems {
+                spawning: false,
+                ..default()
+            }));
+        assert_eq!(creeps, 0);
+    }
+
+    #[test]
+    fn partial_config_keeps_defaults() {
+        let config: GameConfig =
+            serde_json::from_str(r#"{"starting_gold": 42, "systems": {"spawning": false}}"#)
+                .unwrap();
+        assert_eq!(config.starting_gold, 42);
+        assert_eq!(config.starting_lives, GameData::default().lives);
+        assert!(!config.systems.spawning);
+        assert!(config.systems.turrets);
+        assert!(config.tick_rate.is_none());
+    }
+}
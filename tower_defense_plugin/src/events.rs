@@ -8,6 +8,13 @@ pub struct PlaceTurretMessage {
     pub position: IVec2,
 }
 
+/// Place a line of walls, either all of them or none
+#[derive(Message)]
+pub struct PlaceWallsMessage {
+    pub from: IVec2,
+    pub to: IVec2,
+}
+
 #[derive(Message)]
 pub struct NewTurretMessage {
     pub turret_type: TurretType,
@@ -17,8 +24,58 @@ pub struct NewTurretMessage {
 #[derive(Message)]
 pub struct MapChangedMessage;
 
+/// The whole game was replaced, by a saved game or a new map
+#[derive(Message)]
+pub struct GameLoadedMessage;
+
+/// Shot from `origin` to `target`, in world coordinates
 #[derive(Message)]
 pub struct BasicFireMessage {
-    pub origin: IVec2,
+    pub origin: Vec2,
     pub target: Vec2,
+    pub is_crit: bool,
+}
+
+/// A projectile or a follower bullet reached the creep at `target`, in world coordinates
+#[derive(Message)]
+pub struct BulletHitMessage {
+    pub target: Vec2,
+    pub is_crit: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BeamState {
+    Start,
+    Update,
+    Stop,
+}
+
+/// A beam turret locked onto a creep, kept firing at it, or lost it
+#[derive(Message)]
+pub struct BeamMessage {
+    pub turret: Entity,
+    pub state: BeamState,
+    pub origin: Vec2,
+    pub target: Vec2,
+    /// Multiplier of the turret damage reached by the beam
+    pub ramp: f32,
+}
+
+#[derive(Message)]
+pub struct CreepDamagedMessage {
+    pub turret_type: TurretType,
+    pub damage: f32,
+}
+
+#[derive(Message)]
+pub struct CreepKilledMessage {
+    pub creep: Entity,
+    pub wave: usize,
+    pub bounty: i32,
+}
+
+#[derive(Message)]
+pub struct CreepLeakedMessage {
+    pub creep: Entity,
+    pub wave: usize,
 }
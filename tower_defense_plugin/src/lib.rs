@@ -1,87 +1,211 @@
+use bevy::ecs::schedule::ScheduleLabel;
 use bevy::prelude::*;
 
 pub mod components;
+pub mod config;
 pub mod events;
 pub mod map;
+use config::*;
 pub use map::*;
-use resources::{CreepRng, GameData};
+use resources::{CreepRng, CreepSpatialIndex, GridLayout, SpawnTimer, TurretRng};
 use systems::*;
 pub mod resources;
+pub mod save;
 mod systems;
 mod utils;
 pub use utils::*;
 
-pub struct TowerDefensePlugin;
+/// Game logic, configured through the `with_*` builder methods
+#[derive(Default)]
+pub struct TowerDefensePlugin {
+    pub config: GameConfig,
+}
 
-impl Plugin for TowerDefensePlugin {
-    fn build(&self, app: &mut App) {
-        // Add events
-        insert_common_events(app);
+impl TowerDefensePlugin {
+    pub fn new(config: GameConfig) -> Self {
+        Self { config }
+    }
 
-        // Insert resources
-        insert_common_resources(app);
-        app.insert_resource(FreeMap::default());
+    pub fn with_starting_gold(mut self, gold: i32) -> Self {
+        self.config.starting_gold = gold;
+        self
+    }
 
-        // Add systems
-        insert_common_systems(app);
-        app.add_systems(
-            Update,
-            (
-                spawn_creeps::<FreeMap>,
-                handle_turret_placement::<FreeMap>,
-                update_creep_paths::<FreeMap>,
-            ),
-        );
+    pub fn with_starting_lives(mut self, lives: i32) -> Self {
+        self.config.starting_lives = lives;
+        self
+    }
+
+    pub fn with_difficulty(mut self, difficulty: Difficulty) -> Self {
+        self.config.difficulty = difficulty;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn with_map(mut self, map: MapSource) -> Self {
+        self.config.map = map;
+        self
+    }
+
+    pub fn with_tick_rate(mut self, tick_rate: f64) -> Self {
+        self.config.tick_rate = Some(tick_rate);
+        self
+    }
+
+    pub fn with_systems(mut self, systems: EnabledSystems) -> Self {
+        self.config.systems = systems;
+        self
+    }
+
+    pub fn with_layout(mut self, layout: GridLayout) -> Self {
+        self.config.layout = layout;
+        self
+    }
+
+    pub fn with_path_smoothing(mut self, smooth: bool) -> Self {
+        self.config.smooth_paths = smooth;
+        self
+    }
+
+    pub fn with_basic_projectiles(mut self, speed: f32) -> Self {
+        self.config.basic_projectile_speed = Some(speed);
+        self
     }
 }
-pub struct TowerDefensePluginSimpleMap;
 
-impl Plugin for TowerDefensePluginSimpleMap {
+impl Plugin for TowerDefensePlugin {
     fn build(&self, app: &mut App) {
         // Add events
-        insert_common_events(app);
+        insert_events(app);
 
         // Insert resources
-        insert_common_resources(app);
-        app.insert_resource(SimpleMap::default());
+        insert_resources(app, &self.config);
 
         // Add systems
-        insert_common_systems(app);
+        insert_systems(app, &self.config);
+    }
+}
+
+fn insert_systems(app: &mut App, config: &GameConfig) {
+    // The simulation either follows the frame rate or a fixed tick rate
+    let (update, post_update) = match config.tick_rate {
+        Some(_) => (FixedUpdate.intern(), FixedPostUpdate.intern()),
+        None => (Update.intern(), PostUpdate.intern()),
+    };
+
+    app.add_systems(Startup, setup);
+    // Systems reacting to messages run every frame so that none is missed between two ticks
+    if config.systems.placement {
         app.add_systems(
             Update,
             (
-                spawn_creeps::<SimpleMap>,
-                handle_turret_placement::<SimpleMap>,
+                handle_turret_placement,
+                handle_wall_placement,
+                apply_auras
+                    .after(handle_turret_placement)
+                    .after(handle_wall_placement),
             ),
         );
     }
+    if config.systems.spawning {
+        app.add_systems(update, spawn_creeps);
+    }
+    if config.systems.movement {
+        app.add_systems(Update, update_creep_paths);
+        app.add_systems(update, (move_creeps, despawn_slowdown));
+    }
+    if config.systems.turrets {
+        app.add_systems(update, update_creep_index.after(move_creeps));
+        app.add_systems(update, aim_turrets.after(update_creep_index));
+        app.add_systems(
+            update,
+            (
+                basic_turret_system,
+                bomb_turret_system,
+                slow_turret_system,
+                beam_turret_system,
+                chain_turret_system,
+                mortar_turret_system,
+                move_mortar_shells,
+                projectile_turret_system,
+                move_projectiles,
+                move_follower_bullets,
+                bullet_thrower_system,
+            )
+                .after(aim_turrets),
+        );
+    }
+    app.add_systems(post_update, (despawn_dead_creeps, despawn_leaked_creeps));
 }
 
-fn insert_common_systems(app: &mut App) {
-    app.add_systems(Startup, setup);
-    app.add_systems(
-        Update,
-        (
-            move_creeps,
-            basic_turret_system,
-            bomb_turret_system,
-            slow_turret_system,
-            move_follower_bullets,
-            bullet_thrower_system,
-            despawn_slowdown,
+fn insert_resources(app: &mut App, config: &GameConfig) {
+    match &config.map {
+        // Keep the map if it was inserted before the plugin
+        MapSource::Default => {
+            app.init_resource::<ActiveMap>();
+        }
+        MapSource::File(path) => match load_valid_map(path) {
+            Ok(map_file) => {
+                app.insert_resource(ActiveMap::from(map_file));
+            }
+            Err(err) => {
+                println!("Invalid map {path:?}: {err}, running the default map");
+                app.init_resource::<ActiveMap>();
+            }
+        },
+        MapSource::Loaded(map_file) => {
+            app.insert_resource(ActiveMap::from(map_file.clone()));
+        }
+    }
+
+    // The grid follows the shape of the map
+    let layout = GridLayout {
+        shape: app.world().resource::<ActiveMap>().shape(),
+        ..config.layout
+    };
+    app.insert_resource(layout);
+
+    let (creep_rng, turret_rng) = match config.seed {
+        // Both generators are seeded differently to draw different sequences
+        Some(seed) => (
+            CreepRng::from_seed(seed),
+            TurretRng::from_seed(seed.wrapping_add(1)),
         ),
-    );
-    app.add_systems(PostUpdate, despawn_dead_creeps);
+        None => (CreepRng::default(), TurretRng::default()),
+    };
+
+    app.insert_resource(config.starting_game_data())
+        .insert_resource(SpawnTimer::default())
+        .insert_resource(creep_rng)
+        .insert_resource(turret_rng)
+        .init_resource::<CreepSpatialIndex>()
+        .insert_resource(config.clone());
+
+    if let Some(tick_rate) = config.tick_rate {
+        app.insert_resource(Time::<Fixed>::from_hz(tick_rate));
+    }
 }
 
-fn insert_common_resources(app: &mut App) {
-    app.insert_resource(GameData::default())
-        .insert_resource(CreepRng::default());
+fn load_valid_map(path: &std::path::Path) -> Result<MapFile, String> {
+    let map_file = MapFile::load(path)?;
+    map_file.validate().map_err(|err| err.to_string())?;
+    Ok(map_file)
 }
 
-fn insert_common_events(app: &mut App) {
+fn insert_events(app: &mut App) {
     app.add_message::<events::PlaceTurretMessage>()
+        .add_message::<events::PlaceWallsMessage>()
         .add_message::<events::NewTurretMessage>()
         .add_message::<events::BasicFireMessage>()
-        .add_message::<events::MapChangedMessage>();
+        .add_message::<events::BulletHitMessage>()
+        .add_message::<events::BeamMessage>()
+        .add_message::<events::MapChangedMessage>()
+        .add_message::<events::GameLoadedMessage>()
+        .add_message::<events::CreepDamagedMessage>()
+        .add_message::<events::CreepKilledMessage>()
+        .add_message::<events::CreepLeakedMessage>();
 }
@@ -0,0 +1,250 @@
+use bevy::{math::ivec2, prelude::*};
+use rand::{Rng, SeedableRng, rngs::SmallRng, seq::SliceRandom};
+
+use super::*;
+
+/// Number of dead ends explored before trying another start for a road
+const ROAD_BUDGET: u32 = 10_000;
+const ROAD_ATTEMPTS: u32 = 20;
+
+/// Seeded generator of maps whose path between start and end has at least `path_length` cells
+pub struct MapGenerator {
+    pub kind: MapKind,
+    pub seed: u64,
+    pub path_length: usize,
+    /// Share of the cells of free maps covered by scattered obstacles
+    pub obstacle_density: f32,
+}
+
+impl MapGenerator {
+    pub fn new(kind: MapKind, seed: u64) -> Self {
+        Self {
+            kind,
+            seed,
+            path_length: 25,
+            obstacle_density: 0.1,
+        }
+    }
+
+    pub fn with_path_length(mut self, path_length: usize) -> Self {
+        self.path_length = path_length;
+        self
+    }
+
+    pub fn with_obstacle_density(mut self, obstacle_density: f32) -> Self {
+        self.obstacle_density = obstacle_density;
+        self
+    }
+
+    pub fn generate(&self) -> Result<MapFile, String> {
+        if self.path_length < 2 || self.path_length > GRID_WIDTH * GRID_HEIGHT {
+            return Err(format!("Invalid path length {}", self.path_length));
+        }
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        match self.kind {
+            MapKind::Simple => self.generate_road(&mut rng),
+            MapKind::Free | MapKind::Hex => self.generate_obstacles(&mut rng),
+        }
+    }
+
+    /// Winding road which never touches itself, creeps follow its turns
+    fn generate_road(&self, rng: &mut SmallRng) -> Result<MapFile, String> {
+        for _ in 0..ROAD_ATTEMPTS {
+            let mut road = vec![ivec2(0, rng.random_range(0..GRID_HEIGHT as i32))];
+            let mut budget = ROAD_BUDGET;
+            if !extend_road(&mut road, self.path_length, rng, &mut budget) {
+                continue;
+            }
+
+            let mut base = BaseMap {
+                start: road[0],
+                end: *road.last().unwrap(),
+                path: validation::corner_waypoints(&road),
+                ..BaseMap::default()
+            };
+            for cell in &road {
+                base.cells[cell.x as usize][cell.y as usize] = ROAD;
+            }
+            return Ok(MapFile {
+                kind: MapKind::Simple,
+                map: base,
+            });
+        }
+        Err(format!(
+            "Could not generate a road of {} cells",
+            self.path_length
+        ))
+    }
+
+    /// Scattered obstacles, then obstacles on the path until creeps have to walk far enough
+    fn generate_obstacles(&self, rng: &mut SmallRng) -> Result<MapFile, String> {
+        let base = BaseMap {
+            start: ivec2(0, rng.random_range(0..GRID_HEIGHT as i32)),
+            end: ivec2(
+                GRID_WIDTH as i32 - 1,
+                rng.random_range(0..GRID_HEIGHT as i32),
+            ),
+            ..BaseMap::default()
+        };
+        let mut map = ActiveMap::from(MapFile {
+            kind: self.kind,
+            map: base,
+        });
+
+        // Obstacles never close the path, like towers
+        let mut cells: Vec<IVec2> = (0..GRID_WIDTH as i32)
+            .flat_map(|x| (0..GRID_HEIGHT as i32).map(move |y| ivec2(x, y)))
+            .collect();
+        cells.shuffle(rng);
+        let scattered = (cells.len() as f32 * self.obstacle_density) as usize;
+        for cell in cells.into_iter().take(scattered) {
+            if map.is_turret_possible(&[cell]) {
+                map.place_tower(&[cell]);
+            }
+        }
+
+        while map.get_path().len() < self.path_length {
+            let path = map.get_path();
+            let candidates: Vec<IVec2> = path[1..path.len() - 1]
+                .iter()
+                .copied()
+                .filter(|cell| map.is_turret_possible(&[*cell]))
+                .collect();
+            let Some(cell) = candidates.get(rng.random_range(0..candidates.len().max(1))) else {
+                return Err(format!(
+                    "Could not generate a path of {} cells",
+                    self.path_length
+                ));
+            };
+            map.place_tower(&[*cell]);
+        }
+
+        let mut file = map.snapshot();
+        for cell in file.map.cells.iter_mut().flatten() {
+            if *cell != 0 {
+                *cell = OBSTACLE;
+            }
+        }
+        Ok(file)
+    }
+}
+
+/// Depth first search of a road with the target number of cells
+fn extend_road(road: &mut Vec<IVec2>, length: usize, rng: &mut SmallRng, budget: &mut u32) -> bool {
+    if road.len() >= length {
+        return true;
+    }
+    if *budget == 0 {
+        return false;
+    }
+    *budget -= 1;
+
+    let current = *road.last().unwrap();
+    let mut next: Vec<IVec2> = road_neighbours(current)
+        .into_iter()
+        .filter(|cell| can_extend(road, cell))
+        .collect();
+    next.shuffle(rng);
+    for cell in next {
+        road.push(cell);
+        if extend_road(road, length, rng, budget) {
+            return true;
+        }
+        road.pop();
+    }
+    false
+}
+
+fn road_neighbours(cell: IVec2) -> [IVec2; 4] {
+    [ivec2(1, 0), ivec2(-1, 0), ivec2(0, 1), ivec2(0, -1)].map(|direction| cell + direction)
+}
+
+/// The road can go on a cell which only touches its current end
+fn can_extend(road: &[IVec2], cell: &IVec2) -> bool {
+    let current = road.last().unwrap();
+    cell.x >= 0
+        && cell.y >= 0
+        && cell.x < GRID_WIDTH as i32
+        && cell.y < GRID_HEIGHT as i32
+        && !road.contains(cell)
+        && road_neighbours(*cell)
+            .iter()
+            .all(|neighbour| neighbour == current || !road.contains(neighbour))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_roads() {
+        for seed in 0..20 {
+            let file = MapGenerator::new(MapKind::Simple, seed)
+                .with_path_length(40)
+                .generate()
+                .unwrap();
+            let base = &file.map;
+            let roads = base.cells.iter().flatten().filter(|c| **c == ROAD).count();
+            assert_eq!(roads, 40);
+
+            // Creeps walk in straight lines on the road between waypoints
+            assert_eq!(base.path.first(), Some(&base.start));
+            assert_eq!(base.path.last(), Some(&base.end));
+            let mut walked = 1;
+            for step in base.path.windows(2) {
+                let direction = (step[1] - step[0]).signum();
+                assert_eq!(direction.abs().element_sum(), 1);
+                let mut cell = step[0];
+                while cell != step[1] {
+                    cell += direction;
+                    walked += 1;
+                    assert_eq!(base.cells[cell.x as usize][cell.y as usize], ROAD);
+                }
+            }
+            assert_eq!(walked, 40);
+
+            // The road is a single line, its waypoints can be derived again
+            assert_eq!(
+                validation::derive_waypoints(&base.cells, base.start, base.end).as_ref(),
+                Ok(&base.path)
+            );
+            assert_eq!(file.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn generated_obstacles() {
+        for kind in [MapKind::Free, MapKind::Hex] {
+            for seed in 0..20 {
+                let file = MapGenerator::new(kind, seed)
+                    .with_path_length(18)
+                    .generate()
+                    .unwrap();
+                let map = ActiveMap::from(file.clone());
+                assert!(map.get_path().len() >= 18);
+                assert_eq!(map.get_path().first(), Some(&file.map.start));
+                assert_eq!(map.get_path().last(), Some(&file.map.end));
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let generate = |seed| {
+            let file = MapGenerator::new(MapKind::Free, seed).generate().unwrap();
+            serde_json::to_string(&file).unwrap()
+        };
+        assert_eq!(generate(3), generate(3));
+        assert_ne!(generate(3), generate(4));
+    }
+
+    #[test]
+    fn impossible_length() {
+        assert!(
+            MapGenerator::new(MapKind::Simple, 0)
+                .with_path_length(90)
+                .generate()
+                .is_err()
+        );
+    }
+}
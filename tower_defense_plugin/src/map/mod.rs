@@ -0,0 +1,838 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::{math::ivec2, prelude::*};
+use pathfinding::prelude::astar;
+use serde::{Deserialize, Serialize};
+
+use crate::resources::{GridShape, round_axial};
+
+pub mod generator;
+pub mod validation;
+
+pub const GRID_WIDTH: usize = 10;
+pub const GRID_HEIGHT: usize = 10;
+
+/// Cell on which creeps walk on simple maps
+pub const ROAD: u8 = 1;
+/// Cell blocked from the start, on which nothing can be built
+pub const OBSTACLE: u8 = 2;
+
+/// Towers are placed on all the cells of their footprint at once
+pub trait Map {
+    fn place_tower(&mut self, cells: &[IVec2]) -> bool;
+    fn remove_tower(&mut self, cells: &[IVec2]);
+    fn is_turret_possible(&self, cells: &[IVec2]) -> bool;
+    fn get_path(&self) -> &Vec<IVec2>;
+    fn get_start(&self) -> IVec2;
+    fn get_end(&self) -> IVec2;
+    fn snapshot(&self) -> MapFile;
+
+    fn shape(&self) -> GridShape {
+        GridShape::Square
+    }
+
+    /// Maps on which creeps can be rerouted return themselves
+    fn as_dynamic(&self) -> Option<&dyn DynamicMap> {
+        None
+    }
+}
+
+pub trait DynamicMap {
+    fn compute_path(&self, start: &IVec2) -> Option<(Vec<IVec2>, u32)> {
+        self.compute_path_blocked(start, &[])
+    }
+
+    /// Path the creeps would take if towers were placed on `blocked`
+    fn compute_path_blocked(&self, start: &IVec2, blocked: &[IVec2]) -> Option<(Vec<IVec2>, u32)>;
+
+    /// Whether a straight line between the centers of two cells crosses no tower
+    fn line_of_sight(&self, from: &IVec2, to: &IVec2) -> bool;
+
+    /// Keep only the cells of the path where creeps have to turn (string pulling)
+    fn smooth_path(&self, path: &[IVec2]) -> Vec<IVec2> {
+        let Some((&first, rest)) = path.split_first() else {
+            return vec![];
+        };
+        let mut smoothed = vec![first];
+        let mut previous = first;
+        for &next in rest {
+            let anchor = *smoothed.last().unwrap();
+            if previous != anchor && !self.line_of_sight(&anchor, &next) {
+                smoothed.push(previous);
+            }
+            previous = next;
+        }
+        if previous != first {
+            smoothed.push(previous);
+        }
+        smoothed
+    }
+}
+
+/// The map currently played, it can be replaced at runtime
+#[derive(Resource, Deref, DerefMut)]
+pub struct ActiveMap(pub Box<dyn Map + Send + Sync>);
+
+impl Default for ActiveMap {
+    fn default() -> Self {
+        Self(Box::new(SimpleMap::default()))
+    }
+}
+
+impl From<MapFile> for ActiveMap {
+    fn from(file: MapFile) -> Self {
+        match file.kind {
+            MapKind::Simple => Self(Box::new(SimpleMap::new(file.map))),
+            MapKind::Free => Self(Box::new(FreeMap::new(file.map))),
+            MapKind::Hex => Self(Box::new(HexMap::new(file.map))),
+        }
+    }
+}
+
+const STRAIGHT_COST: u32 = 50;
+const DIAGONAL_COST: u32 = 75;
+
+/// Moves allowed to creeps finding their own path on square maps
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MovementRules {
+    FourWay,
+    /// Diagonal moves are only allowed when both adjacent cells are empty
+    #[default]
+    EightWay,
+    /// Diagonal moves can squeeze between two towers
+    EightWayCornerCutting,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BaseMap {
+    pub cells: [[u8; GRID_HEIGHT]; GRID_WIDTH],
+    pub start: IVec2,
+    pub end: IVec2,
+    #[serde(default)]
+    pub path: Vec<IVec2>,
+    /// Ignored by hexagonal maps
+    #[serde(default)]
+    pub movement: MovementRules,
+}
+
+impl BaseMap {
+    /// Content of the cell, `None` outside of the map
+    pub fn cell(&self, pos: &IVec2) -> Option<u8> {
+        if pos.x < 0 || pos.y < 0 || pos.x >= GRID_WIDTH as i32 || pos.y >= GRID_HEIGHT as i32 {
+            return None;
+        }
+        Some(self.cells[pos.x as usize][pos.y as usize])
+    }
+
+    fn is_empty(&self, pos: &IVec2) -> bool {
+        pos.x >= 0
+            && pos.y >= 0
+            && pos.x < GRID_WIDTH as i32
+            && pos.y < GRID_HEIGHT as i32
+            && self.cells[pos.x as usize][pos.y as usize] == 0
+    }
+
+    /// Lower bound of the cost between two cells, keeping A* optimal
+    fn heuristic(&self, start: &IVec2, end: &IVec2) -> u32 {
+        let dx = start.x.abs_diff(end.x);
+        let dy = start.y.abs_diff(end.y);
+        match self.movement {
+            // Manhattan distance
+            MovementRules::FourWay => STRAIGHT_COST * (dx + dy),
+            // Octile distance
+            MovementRules::EightWay | MovementRules::EightWayCornerCutting => {
+                STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+            }
+        }
+    }
+
+    fn successors(&self, pos: &IVec2) -> Vec<(IVec2, u32)> {
+        let straight = [ivec2(1, 0), ivec2(-1, 0), ivec2(0, 1), ivec2(0, -1)]
+            .into_iter()
+            .map(|direction| pos + direction)
+            .filter(|p| self.is_empty(p))
+            .map(|p| (p, STRAIGHT_COST));
+
+        let diagonals = match self.movement {
+            MovementRules::FourWay => vec![],
+            MovementRules::EightWay | MovementRules::EightWayCornerCutting => {
+                vec![ivec2(1, 1), ivec2(-1, 1), ivec2(1, -1), ivec2(-1, -1)]
+            }
+        };
+        let diag = diagonals
+            .into_iter()
+            .filter(|direction| {
+                self.is_empty(&(pos + direction))
+                    && (self.movement == MovementRules::EightWayCornerCutting
+                        || (self.is_empty(&ivec2(pos.x + direction.x, pos.y))
+                            && self.is_empty(&ivec2(pos.x, pos.y + direction.y))))
+            })
+            .map(|direction| (pos + direction, DIAGONAL_COST));
+
+        straight.chain(diag).collect()
+    }
+
+    /// Cells are closed squares so that lines touching a tower corner are blocked
+    fn line_of_sight(&self, from: &IVec2, to: &IVec2) -> bool {
+        let (start, end) = (from.as_vec2(), to.as_vec2());
+        let min = from.min(*to);
+        let max = from.max(*to);
+        (min.x..=max.x).all(|x| {
+            (min.y..=max.y).all(|y| {
+                let cell = ivec2(x, y);
+                self.is_empty(&cell) || !segment_touches_cell(start, end, cell)
+            })
+        })
+    }
+
+    fn place_tower(&mut self, cells: &[IVec2]) -> bool {
+        if !cells.iter().all(|pos| self.is_empty(pos)) {
+            return false;
+        }
+        for pos in cells {
+            self.cells[pos.x as usize][pos.y as usize] = u8::MAX;
+        }
+        true
+    }
+
+    /// Towers can not be placed on the start or the end, nor close the path between them
+    fn keeps_path(&self, cells: &[IVec2], map: &dyn DynamicMap) -> bool {
+        cells.iter().all(|pos| self.is_empty(pos))
+            && !cells.contains(&self.end)
+            && !cells.contains(&self.start)
+            && map.compute_path_blocked(&self.start, cells).is_some()
+    }
+}
+
+fn without_blocked(mut successors: Vec<(IVec2, u32)>, blocked: &[IVec2]) -> Vec<(IVec2, u32)> {
+    successors.retain(|(p, _)| !blocked.contains(p));
+    successors
+}
+
+/// Slab test between a segment and the unit square centered on a cell, borders included
+fn segment_touches_cell(start: Vec2, end: Vec2, cell: IVec2) -> bool {
+    let half_size = 0.5 + 1e-4;
+    let (min, max) = (cell.as_vec2() - half_size, cell.as_vec2() + half_size);
+    let direction = end - start;
+    let (mut enter, mut exit) = (0.0_f32, 1.0_f32);
+    for axis in 0..2 {
+        if direction[axis] == 0.0 {
+            if start[axis] < min[axis] || start[axis] > max[axis] {
+                return false;
+            }
+        } else {
+            let t1 = (min[axis] - start[axis]) / direction[axis];
+            let t2 = (max[axis] - start[axis]) / direction[axis];
+            enter = enter.max(t1.min(t2));
+            exit = exit.min(t1.max(t2));
+        }
+    }
+    enter <= exit
+}
+
+impl Default for BaseMap {
+    fn default() -> Self {
+        Self {
+            cells: [[0; GRID_HEIGHT]; GRID_WIDTH],
+            start: ivec2(0, 0),
+            end: ivec2(9, 9),
+            path: vec![],
+            movement: MovementRules::default(),
+        }
+    }
+}
+
+macro_rules! impl_map {
+    ($kind: expr) => {
+        fn remove_tower(&mut self, cells: &[IVec2]) {
+            for pos in cells {
+                self.base.cells[pos.x as usize][pos.y as usize] = 0;
+            }
+        }
+
+        fn get_path(&self) -> &Vec<IVec2> {
+            &self.base.path
+        }
+
+        fn get_start(&self) -> IVec2 {
+            self.base.start
+        }
+
+        fn get_end(&self) -> IVec2 {
+            self.base.end
+        }
+
+        fn snapshot(&self) -> MapFile {
+            MapFile {
+                kind: $kind,
+                map: self.base.clone(),
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MapKind {
+    /// Creeps follow the waypoints stored in the path
+    Simple,
+    /// Creeps find their way around the turrets, the path is computed on load
+    Free,
+    /// Same as `Free` on hexagons, cells are stored by axial coordinates
+    Hex,
+}
+
+impl MapKind {
+    pub fn shape(&self) -> GridShape {
+        match self {
+            MapKind::Simple | MapKind::Free => GridShape::Square,
+            MapKind::Hex => GridShape::Hex,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapFile {
+    pub kind: MapKind,
+    #[serde(flatten)]
+    pub map: BaseMap,
+}
+
+impl MapFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        serde_json::from_str(&json).map_err(|err| err.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(path, json).map_err(|err| err.to_string())
+    }
+}
+
+pub struct SimpleMap {
+    base: BaseMap,
+}
+
+impl SimpleMap {
+    pub fn new(base: BaseMap) -> Self {
+        Self { base }
+    }
+}
+
+impl Default for SimpleMap {
+    fn default() -> Self {
+        let cells = [
+            [0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 1, 0, 1, 1, 1, 1, 1, 1, 0],
+            [0, 1, 0, 1, 0, 0, 0, 0, 1, 0],
+            [0, 1, 0, 1, 1, 1, 1, 0, 1, 0],
+            [0, 1, 0, 0, 0, 0, 1, 0, 1, 0],
+            [0, 1, 0, 0, 0, 0, 1, 0, 1, 0],
+            [0, 1, 0, 1, 1, 1, 1, 0, 1, 0],
+            [0, 1, 0, 0, 0, 0, 0, 0, 1, 0],
+            [0, 1, 1, 1, 1, 1, 1, 1, 1, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ];
+        let start = ivec2(0, 1);
+        let end = ivec2(6, 3);
+        Self {
+            base: BaseMap {
+                cells,
+                start,
+                end,
+                path: validation::derive_waypoints(&cells, start, end)
+                    .expect("the default road is a single line"),
+                movement: MovementRules::default(),
+            },
+        }
+    }
+}
+
+impl Map for SimpleMap {
+    fn place_tower(&mut self, cells: &[IVec2]) -> bool {
+        self.base.place_tower(cells)
+    }
+
+    fn is_turret_possible(&self, cells: &[IVec2]) -> bool {
+        cells.iter().all(|pos| self.base.is_empty(pos))
+    }
+
+    impl_map!(MapKind::Simple);
+}
+
+pub struct FreeMap {
+    base: BaseMap,
+}
+
+impl Default for FreeMap {
+    fn default() -> Self {
+        Self {
+            base: BaseMap {
+                path: vec![
+                    IVec2 { x: 0, y: 0 },
+                    IVec2 { x: 1, y: 1 },
+                    IVec2 { x: 2, y: 2 },
+                    IVec2 { x: 3, y: 3 },
+                    IVec2 { x: 4, y: 4 },
+                    IVec2 { x: 5, y: 5 },
+                    IVec2 { x: 6, y: 6 },
+                    IVec2 { x: 7, y: 7 },
+                    IVec2 { x: 8, y: 8 },
+                    IVec2 { x: 9, y: 9 },
+                ],
+                ..BaseMap::default()
+            },
+        }
+    }
+}
+
+impl Map for FreeMap {
+    fn place_tower(&mut self, cells: &[IVec2]) -> bool {
+        if self.base.place_tower(cells) {
+            self.recompute_path();
+            return true;
+        }
+        false
+    }
+
+    fn is_turret_possible(&self, cells: &[IVec2]) -> bool {
+        self.base.keeps_path(cells, self)
+    }
+
+    fn as_dynamic(&self) -> Option<&dyn DynamicMap> {
+        Some(self)
+    }
+
+    impl_map!(MapKind::Free);
+}
+
+impl DynamicMap for FreeMap {
+    fn compute_path_blocked(&self, start: &IVec2, blocked: &[IVec2]) -> Option<(Vec<IVec2>, u32)> {
+        astar(
+            start,
+            |p| without_blocked(self.base.successors(p), blocked),
+            |p| self.base.heuristic(p, &self.base.end),
+            |p| *p == self.base.end,
+        )
+    }
+
+    fn line_of_sight(&self, from: &IVec2, to: &IVec2) -> bool {
+        self.base.line_of_sight(from, to)
+    }
+}
+
+impl FreeMap {
+    pub fn new(base: BaseMap) -> Self {
+        let mut map = Self { base };
+        map.recompute_path();
+        map
+    }
+
+    pub fn recompute_path(&mut self) {
+        if let Some((path, _)) = self.compute_path(&self.base.start) {
+            self.base.path = path
+        } else {
+            self.base.path = vec![]
+        }
+    }
+}
+
+/// Free map on hexagons, positions are axial coordinates
+pub struct HexMap {
+    base: BaseMap,
+}
+
+impl Default for HexMap {
+    fn default() -> Self {
+        Self::new(BaseMap::default())
+    }
+}
+
+impl Map for HexMap {
+    fn place_tower(&mut self, cells: &[IVec2]) -> bool {
+        if self.base.place_tower(cells) {
+            self.recompute_path();
+            return true;
+        }
+        false
+    }
+
+    fn is_turret_possible(&self, cells: &[IVec2]) -> bool {
+        self.base.keeps_path(cells, self)
+    }
+
+    fn shape(&self) -> GridShape {
+        GridShape::Hex
+    }
+
+    fn as_dynamic(&self) -> Option<&dyn DynamicMap> {
+        Some(self)
+    }
+
+    impl_map!(MapKind::Hex);
+}
+
+impl DynamicMap for HexMap {
+    fn compute_path_blocked(&self, start: &IVec2, blocked: &[IVec2]) -> Option<(Vec<IVec2>, u32)> {
+        astar(
+            start,
+            |p| without_blocked(self.successors(p), blocked),
+            |p| HexMap::distance(p, &self.base.end) * HEX_STEP_COST,
+            |p| *p == self.base.end,
+        )
+    }
+
+    fn line_of_sight(&self, from: &IVec2, to: &IVec2) -> bool {
+        // Sample every hexagon crossed by the line, nudged both ways to catch the ones it only grazes
+        let steps = HexMap::distance(from, to).max(1);
+        let (from, to) = (from.as_vec2(), to.as_vec2());
+        (0..=steps).all(|step| {
+            let point = from.lerp(to, step as f32 / steps as f32);
+            [1e-3, -1e-3].into_iter().all(|nudge| {
+                let cell = round_axial(point + Vec2::new(nudge, nudge * 2.0));
+                self.base.is_empty(&cell)
+            })
+        })
+    }
+}
+
+const HEX_STEP_COST: u32 = 50;
+
+impl HexMap {
+    pub fn new(base: BaseMap) -> Self {
+        let mut map = Self { base };
+        map.recompute_path();
+        map
+    }
+
+    pub fn neighbours(pos: &IVec2) -> [IVec2; 6] {
+        [
+            ivec2(1, 0),
+            ivec2(1, -1),
+            ivec2(0, -1),
+            ivec2(-1, 0),
+            ivec2(-1, 1),
+            ivec2(0, 1),
+        ]
+        .map(|direction| pos + direction)
+    }
+
+    /// Number of steps between two hexagons
+    pub fn distance(start: &IVec2, end: &IVec2) -> u32 {
+        let diff = end - start;
+        (diff.x.unsigned_abs() + diff.y.unsigned_abs() + (diff.x + diff.y).unsigned_abs()) / 2
+    }
+
+    fn successors(&self, pos: &IVec2) -> Vec<(IVec2, u32)> {
+        HexMap::neighbours(pos)
+            .into_iter()
+            .filter(|p| self.base.is_empty(p))
+            .map(|p| (p, HEX_STEP_COST))
+            .collect()
+    }
+
+    pub fn recompute_path(&mut self) {
+        if let Some((path, _)) = self.compute_path(&self.base.start) {
+            self.base.path = path
+        } else {
+            self.base.path = vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::TurretType;
+    use pathfinding::prelude::dijkstra;
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    #[test]
+    fn place_tower_twice() {
+        let mut map = FreeMap::default();
+        assert!(map.place_tower(&[IVec2 { x: 0, y: 1 }]));
+        assert!(!map.place_tower(&[IVec2 { x: 0, y: 1 }]));
+    }
+
+    /*
+     This test find the shortest path in this maze
+     sxe....
+     ooo....
+     .......
+    */
+    #[test]
+    fn easy_pathfinding() {
+        let mut map = FreeMap {
+            base: BaseMap {
+                start: ivec2(0, 0),
+                end: ivec2(0, 2),
+                ..BaseMap::default()
+            },
+        };
+        map.place_tower(&[IVec2 { x: 0, y: 1 }]); // This is the tower (x in the example)
+        map.recompute_path();
+        assert_eq!(
+            map.base.path,
+            vec!(
+                IVec2 { x: 0, y: 0 }, // start
+                IVec2 { x: 1, y: 0 },
+                IVec2 { x: 1, y: 1 },
+                IVec2 { x: 1, y: 2 },
+                IVec2 { x: 0, y: 2 }, // end
+            ),
+        );
+    }
+
+    #[test]
+    fn impossible_pathfinding() {
+        let mut map = FreeMap {
+            base: BaseMap {
+                start: ivec2(0, 0),
+                end: ivec2(2, 2),
+                ..BaseMap::default()
+            },
+        };
+        map.place_tower(&[IVec2 { x: 0, y: 1 }]); // This is the tower (x in the example)
+        map.place_tower(&[IVec2 { x: 1, y: 0 }]); // This is the tower (x in the example)
+        map.recompute_path();
+        assert_eq!(map.base.path, vec![]);
+    }
+
+    #[test]
+    fn hex_distance() {
+        assert_eq!(HexMap::distance(&ivec2(0, 0), &ivec2(3, 0)), 3);
+        assert_eq!(HexMap::distance(&ivec2(0, 0), &ivec2(3, -3)), 3);
+        assert_eq!(HexMap::distance(&ivec2(0, 0), &ivec2(3, 3)), 6);
+        for neighbour in HexMap::neighbours(&ivec2(4, 4)) {
+            assert_eq!(HexMap::distance(&ivec2(4, 4), &neighbour), 1);
+        }
+    }
+
+    /*
+     (3, 2) and (2, 3) are the shared neighbours of (2, 2) and (3, 3),
+     blocking both forces a detour of two more steps
+    */
+    #[test]
+    fn hex_pathfinding() {
+        let mut map = HexMap::new(BaseMap {
+            start: ivec2(2, 2),
+            end: ivec2(3, 3),
+            ..BaseMap::default()
+        });
+        assert_eq!(map.base.path.len(), 3);
+
+        assert!(map.place_tower(&[ivec2(3, 2)]));
+        assert!(map.place_tower(&[ivec2(2, 3)]));
+        assert_eq!(map.base.path.len(), 5);
+        for step in map.base.path.windows(2) {
+            assert_eq!(HexMap::distance(&step[0], &step[1]), 1);
+            assert!(map.base.is_empty(&step[1]));
+        }
+    }
+
+    #[test]
+    fn hex_placement_keeps_a_path() {
+        // (0, 0) only has two neighbours inside the grid
+        let mut map = HexMap::default();
+        assert!(map.is_turret_possible(&[ivec2(1, 0)]));
+        map.place_tower(&[ivec2(1, 0)]);
+        assert!(!map.is_turret_possible(&[ivec2(0, 1)]));
+    }
+
+    fn random_map(rng: &mut SmallRng, movement: MovementRules) -> FreeMap {
+        let mut base = BaseMap {
+            movement,
+            ..BaseMap::default()
+        };
+        for column in base.cells.iter_mut() {
+            for cell in column.iter_mut() {
+                if rng.random_bool(0.3) {
+                    *cell = u8::MAX;
+                }
+            }
+        }
+        base.cells[0][0] = 0;
+        base.cells[9][9] = 0;
+        FreeMap::new(base)
+    }
+
+    #[test]
+    fn optimal_paths() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for movement in [
+            MovementRules::FourWay,
+            MovementRules::EightWay,
+            MovementRules::EightWayCornerCutting,
+        ] {
+            let mut reachable = 0;
+            for _ in 0..200 {
+                let map = random_map(&mut rng, movement);
+                let end = map.base.end;
+                let shortest = dijkstra(&map.base.start, |p| map.base.successors(p), |p| *p == end);
+                let found = map.compute_path(&map.base.start);
+                assert_eq!(
+                    found.as_ref().map(|(_, cost)| *cost),
+                    shortest.map(|(_, cost)| cost)
+                );
+                if let Some((path, _)) = found {
+                    reachable += 1;
+                    for step in path.windows(2) {
+                        assert!(
+                            map.base
+                                .successors(&step[0])
+                                .iter()
+                                .any(|(p, _)| *p == step[1])
+                        );
+                    }
+                }
+            }
+            // Make sure the comparison is not only between missing paths
+            assert!(reachable > 50);
+        }
+    }
+
+    #[test]
+    fn movement_rules() {
+        // Two towers touching by their corners
+        let mut base = BaseMap {
+            start: ivec2(0, 0),
+            end: ivec2(1, 1),
+            ..BaseMap::default()
+        };
+        base.place_tower(&[ivec2(1, 0)]);
+        base.place_tower(&[ivec2(0, 1)]);
+
+        base.movement = MovementRules::EightWayCornerCutting;
+        assert_eq!(FreeMap::new(base.clone()).base.path.len(), 2);
+
+        base.movement = MovementRules::EightWay;
+        assert!(FreeMap::new(base.clone()).base.path.is_empty());
+
+        // Without diagonals, going around costs two straight moves
+        let map = FreeMap::new(BaseMap {
+            movement: MovementRules::FourWay,
+            ..BaseMap::default()
+        });
+        assert_eq!(
+            map.compute_path(&ivec2(0, 0)).unwrap().1,
+            18 * STRAIGHT_COST
+        );
+    }
+
+    /// Points along the smoothed path never fall inside a tower, including its borders
+    fn assert_no_clipping(base: &BaseMap, waypoints: &[IVec2]) {
+        for segment in waypoints.windows(2) {
+            let (start, end) = (segment[0].as_vec2(), segment[1].as_vec2());
+            for step in 0..=1000 {
+                let point = start.lerp(end, step as f32 / 1000.0);
+                // Every cell whose square contains the point, up to its borders
+                for corner in [Vec2::new(0.499, 0.499), Vec2::new(-0.499, 0.499)] {
+                    for nearby in [point + corner, point - corner] {
+                        let cell = nearby.round().as_ivec2();
+                        if (point - cell.as_vec2()).abs().max_element() <= 0.5 {
+                            assert!(base.is_empty(&cell), "{segment:?} clips {cell}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_open_path() {
+        let map = FreeMap::new(BaseMap {
+            start: ivec2(0, 0),
+            end: ivec2(9, 4),
+            ..BaseMap::default()
+        });
+        let smoothed = map.smooth_path(&map.base.path);
+        assert_eq!(smoothed, vec![ivec2(0, 0), ivec2(9, 4)]);
+    }
+
+    /*
+     The path has to turn around the end of the wall
+     s..x....
+     ...x....
+     ...x..e.
+     ........
+    */
+    #[test]
+    fn smooth_path_around_wall() {
+        let mut map = FreeMap::new(BaseMap {
+            start: ivec2(0, 9),
+            end: ivec2(6, 7),
+            ..BaseMap::default()
+        });
+        for y in 7..10 {
+            map.place_tower(&[ivec2(3, y)]);
+        }
+        let smoothed = map.smooth_path(&map.base.path);
+        assert!(smoothed.len() < map.base.path.len());
+        assert_eq!(smoothed.first(), Some(&ivec2(0, 9)));
+        assert_eq!(smoothed.last(), Some(&ivec2(6, 7)));
+        assert_no_clipping(&map.base, &smoothed);
+
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let map = random_map(&mut rng, MovementRules::EightWay);
+            let smoothed = map.smooth_path(&map.base.path);
+            assert!(smoothed.len() <= map.base.path.len());
+            assert_no_clipping(&map.base, &smoothed);
+        }
+    }
+
+    #[test]
+    fn smooth_hex_path() {
+        let map = HexMap::default();
+        let smoothed = map.smooth_path(&map.base.path);
+        assert_eq!(smoothed, vec![ivec2(0, 0), ivec2(9, 9)]);
+    }
+
+    #[test]
+    fn multi_cell_footprint() {
+        let mut map = FreeMap::default();
+        let bomb = TurretType::Bomb.cells(ivec2(4, 4));
+        assert!(map.place_tower(&[ivec2(5, 5)]));
+
+        // Overlapping footprints are refused without placing any cell
+        assert!(!map.is_turret_possible(&bomb));
+        assert!(!map.place_tower(&bomb));
+        assert!(map.base.is_empty(&ivec2(4, 4)));
+
+        map.remove_tower(&[ivec2(5, 5)]);
+        assert!(map.place_tower(&bomb));
+        for cell in &bomb {
+            assert!(!map.base.path.contains(cell));
+        }
+
+        // A footprint closing the last gap of a wall is refused as a whole
+        let mut map = FreeMap::new(BaseMap {
+            start: ivec2(0, 0),
+            end: ivec2(0, 9),
+            ..BaseMap::default()
+        });
+        for x in 2..10 {
+            assert!(map.place_tower(&[ivec2(x, 5)]));
+        }
+        assert!(!map.is_turret_possible(&TurretType::Bomb.cells(ivec2(0, 4))));
+        assert!(map.is_turret_possible(&TurretType::Bomb.cells(ivec2(0, 7))));
+    }
+
+    #[test]
+    fn blocked_path_preview() {
+        let mut map = FreeMap::new(BaseMap {
+            start: ivec2(0, 0),
+            end: ivec2(9, 0),
+            ..BaseMap::default()
+        });
+        let blocked = [ivec2(4, 0), ivec2(4, 1)];
+        let (preview, cost) = map.compute_path_blocked(&ivec2(0, 0), &blocked).unwrap();
+        assert!(preview.iter().all(|cell| !blocked.contains(cell)));
+        assert!(cost > map.compute_path(&ivec2(0, 0)).unwrap().1);
+
+        // The preview is as long as the path once the towers are placed
+        assert!(map.place_tower(&blocked));
+        assert_eq!(map.compute_path(&ivec2(0, 0)).unwrap().1, cost);
+    }
+}
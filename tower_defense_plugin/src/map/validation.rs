@@ -0,0 +1,286 @@
+use std::fmt;
+
+use bevy::{math::ivec2, prelude::*};
+
+use super::*;
+
+/// Why creeps could not walk a map from its start to its end
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MapError {
+    StartOutside(IVec2),
+    EndOutside(IVec2),
+    StartOffRoad(IVec2),
+    EndOffRoad(IVec2),
+    StartBlocked(IVec2),
+    EndBlocked(IVec2),
+    /// The road splits at this cell, creeps would not know where to go
+    RoadBranches(IVec2),
+    /// The road stops at this cell before reaching the end
+    RoadDeadEnd(IVec2),
+    /// This road cell can not be reached from the start
+    RoadDisconnected(IVec2),
+    /// The first waypoint, if any, is not the start
+    PathStartsAt(Option<IVec2>),
+    /// The last waypoint, if any, is not the end
+    PathEndsAt(Option<IVec2>),
+    /// Creeps only walk in straight lines between waypoints
+    WaypointsNotAligned(IVec2, IVec2),
+    /// Creeps would leave the road at this cell
+    PathOffRoad(IVec2),
+    Unreachable,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::StartOutside(pos) => write!(f, "start {pos} is outside of the map"),
+            MapError::EndOutside(pos) => write!(f, "end {pos} is outside of the map"),
+            MapError::StartOffRoad(pos) => write!(f, "start {pos} is not on the road"),
+            MapError::EndOffRoad(pos) => write!(f, "end {pos} is not on the road"),
+            MapError::StartBlocked(pos) => write!(f, "start {pos} is blocked"),
+            MapError::EndBlocked(pos) => write!(f, "end {pos} is blocked"),
+            MapError::RoadBranches(pos) => write!(f, "road branches at {pos}"),
+            MapError::RoadDeadEnd(pos) => write!(f, "road stops at {pos} before the end"),
+            MapError::RoadDisconnected(pos) => {
+                write!(f, "road cell {pos} is not connected to the start")
+            }
+            MapError::PathStartsAt(Some(pos)) => {
+                write!(f, "first waypoint {pos} is not the start")
+            }
+            MapError::PathEndsAt(Some(pos)) => write!(f, "last waypoint {pos} is not the end"),
+            MapError::PathStartsAt(None) | MapError::PathEndsAt(None) => {
+                write!(f, "there are no waypoints")
+            }
+            MapError::WaypointsNotAligned(from, to) => {
+                write!(f, "waypoints {from} and {to} are not aligned")
+            }
+            MapError::PathOffRoad(pos) => write!(f, "path leaves the road at {pos}"),
+            MapError::Unreachable => write!(f, "the end can not be reached from the start"),
+        }
+    }
+}
+
+type Cells = [[u8; GRID_HEIGHT]; GRID_WIDTH];
+
+fn cell(cells: &Cells, pos: IVec2) -> Option<u8> {
+    if pos.x < 0 || pos.y < 0 || pos.x >= GRID_WIDTH as i32 || pos.y >= GRID_HEIGHT as i32 {
+        return None;
+    }
+    Some(cells[pos.x as usize][pos.y as usize])
+}
+
+fn road_neighbours(cells: &Cells, pos: IVec2) -> Vec<IVec2> {
+    [ivec2(1, 0), ivec2(-1, 0), ivec2(0, 1), ivec2(0, -1)]
+        .into_iter()
+        .map(|direction| pos + direction)
+        .filter(|p| cell(cells, *p) == Some(ROAD))
+        .collect()
+}
+
+/// Corner waypoints of a road drawn as a single line of `ROAD` cells from `start` to `end`
+pub fn derive_waypoints(cells: &Cells, start: IVec2, end: IVec2) -> Result<Vec<IVec2>, MapError> {
+    match cell(cells, start) {
+        None => return Err(MapError::StartOutside(start)),
+        Some(ROAD) => {}
+        Some(_) => return Err(MapError::StartOffRoad(start)),
+    }
+    match cell(cells, end) {
+        None => return Err(MapError::EndOutside(end)),
+        Some(ROAD) => {}
+        Some(_) => return Err(MapError::EndOffRoad(end)),
+    }
+
+    let mut road = vec![start];
+    while *road.last().unwrap() != end {
+        let current = *road.last().unwrap();
+        let next: Vec<IVec2> = road_neighbours(cells, current)
+            .into_iter()
+            .filter(|p| road.len() < 2 || *p != road[road.len() - 2])
+            .collect();
+        match next[..] {
+            [] => return Err(MapError::RoadDeadEnd(current)),
+            [next] if road.contains(&next) => return Err(MapError::RoadBranches(next)),
+            [next] => road.push(next),
+            _ => return Err(MapError::RoadBranches(current)),
+        }
+    }
+
+    // Road going on past the end, or left apart
+    if road_neighbours(cells, end).len() > 1 {
+        return Err(MapError::RoadBranches(end));
+    }
+    for x in 0..GRID_WIDTH as i32 {
+        for y in 0..GRID_HEIGHT as i32 {
+            let pos = ivec2(x, y);
+            if cell(cells, pos) == Some(ROAD) && !road.contains(&pos) {
+                return Err(MapError::RoadDisconnected(pos));
+            }
+        }
+    }
+
+    Ok(corner_waypoints(&road))
+}
+
+/// First and last cells of a line of cells and every cell where it turns
+pub fn corner_waypoints(line: &[IVec2]) -> Vec<IVec2> {
+    let mut waypoints: Vec<IVec2> = line.first().copied().into_iter().collect();
+    for step in line.windows(3) {
+        if step[1] - step[0] != step[2] - step[1] {
+            waypoints.push(step[1]);
+        }
+    }
+    if line.len() > 1 {
+        waypoints.push(*line.last().unwrap());
+    }
+    waypoints
+}
+
+impl MapFile {
+    /// Checks that creeps can walk from the start to the end of the map
+    pub fn validate(&self) -> Result<(), MapError> {
+        let base = &self.map;
+        if base.cell(&base.start).is_none() {
+            return Err(MapError::StartOutside(base.start));
+        }
+        if base.cell(&base.end).is_none() {
+            return Err(MapError::EndOutside(base.end));
+        }
+
+        match self.kind {
+            MapKind::Simple => {
+                if base.cell(&base.start) != Some(ROAD) {
+                    return Err(MapError::StartOffRoad(base.start));
+                }
+                if base.cell(&base.end) != Some(ROAD) {
+                    return Err(MapError::EndOffRoad(base.end));
+                }
+                if base.path.first() != Some(&base.start) {
+                    return Err(MapError::PathStartsAt(base.path.first().copied()));
+                }
+                if base.path.last() != Some(&base.end) {
+                    return Err(MapError::PathEndsAt(base.path.last().copied()));
+                }
+                for step in base.path.windows(2) {
+                    let (from, to) = (step[0], step[1]);
+                    if from.x != to.x && from.y != to.y {
+                        return Err(MapError::WaypointsNotAligned(from, to));
+                    }
+                    let direction = (to - from).signum();
+                    let mut pos = from;
+                    while pos != to {
+                        pos += direction;
+                        if base.cell(&pos) != Some(ROAD) {
+                            return Err(MapError::PathOffRoad(pos));
+                        }
+                    }
+                }
+            }
+            MapKind::Free | MapKind::Hex => {
+                if base.cell(&base.start) != Some(0) {
+                    return Err(MapError::StartBlocked(base.start));
+                }
+                if base.cell(&base.end) != Some(0) {
+                    return Err(MapError::EndBlocked(base.end));
+                }
+                if ActiveMap::from(self.clone()).get_path().is_empty() {
+                    return Err(MapError::Unreachable);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simple_map() -> MapFile {
+        SimpleMap::default().snapshot()
+    }
+
+    #[test]
+    fn default_waypoints() {
+        assert_eq!(
+            SimpleMap::default().get_path(),
+            &vec![
+                ivec2(0, 1),
+                ivec2(8, 1),
+                ivec2(8, 8),
+                ivec2(1, 8),
+                ivec2(1, 3),
+                ivec2(3, 3),
+                ivec2(3, 6),
+                ivec2(6, 6),
+                ivec2(6, 3),
+            ]
+        );
+        assert_eq!(simple_map().validate(), Ok(()));
+    }
+
+    #[test]
+    fn road_errors() {
+        let file = simple_map();
+        let (start, end) = (file.map.start, file.map.end);
+        let derive = |cells: &Cells| derive_waypoints(cells, start, end);
+
+        let mut cells = file.map.cells;
+        cells[8][5] = 0;
+        assert_eq!(derive(&cells), Err(MapError::RoadDeadEnd(ivec2(8, 4))));
+
+        let mut cells = file.map.cells;
+        cells[9][5] = ROAD;
+        assert_eq!(derive(&cells), Err(MapError::RoadBranches(ivec2(8, 5))));
+
+        let mut cells = file.map.cells;
+        cells[9][9] = ROAD;
+        assert_eq!(derive(&cells), Err(MapError::RoadDisconnected(ivec2(9, 9))));
+
+        let mut cells = file.map.cells;
+        cells[5][3] = ROAD;
+        assert_eq!(derive(&cells), Err(MapError::RoadBranches(end)));
+
+        assert_eq!(
+            derive_waypoints(&file.map.cells, ivec2(0, 0), end),
+            Err(MapError::StartOffRoad(ivec2(0, 0)))
+        );
+        assert_eq!(
+            derive_waypoints(&file.map.cells, start, ivec2(10, 3)),
+            Err(MapError::EndOutside(ivec2(10, 3)))
+        );
+    }
+
+    #[test]
+    fn path_errors() {
+        let mut file = simple_map();
+        file.map.end = ivec2(4, 6);
+        assert_eq!(
+            file.validate(),
+            Err(MapError::PathEndsAt(Some(ivec2(6, 3))))
+        );
+
+        let mut file = simple_map();
+        file.map.path.remove(1);
+        assert_eq!(
+            file.validate(),
+            Err(MapError::WaypointsNotAligned(ivec2(0, 1), ivec2(8, 8)))
+        );
+
+        let mut file = simple_map();
+        file.map.cells[4][1] = 0;
+        assert_eq!(file.validate(), Err(MapError::PathOffRoad(ivec2(4, 1))));
+
+        let mut file = simple_map();
+        file.map.path.clear();
+        assert_eq!(file.validate(), Err(MapError::PathStartsAt(None)));
+
+        let mut free = FreeMap::default().snapshot();
+        assert_eq!(free.validate(), Ok(()));
+        for y in 0..GRID_HEIGHT {
+            free.map.cells[5][y] = OBSTACLE;
+        }
+        assert_eq!(free.validate(), Err(MapError::Unreachable));
+        free.map.cells[0][0] = OBSTACLE;
+        assert_eq!(free.validate(), Err(MapError::StartBlocked(ivec2(0, 0))));
+    }
+}
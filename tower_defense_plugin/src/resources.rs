@@ -1,7 +1,12 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Resource)]
+#[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct GameData {
     pub score: i32,
     pub lives: i32,
@@ -24,6 +29,14 @@ pub struct CreepRng {
     pub rng: SmallRng,
 }
 
+impl CreepRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
 impl Default for CreepRng {
     fn default() -> Self {
         Self {
@@ -31,3 +44,401 @@ impl Default for CreepRng {
         }
     }
 }
+
+/// Random generator of the damage rolls, kept apart from the creeps one
+#[derive(Resource)]
+pub struct TurretRng {
+    pub rng: SmallRng,
+}
+
+impl TurretRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for TurretRng {
+    fn default() -> Self {
+        Self {
+            rng: SmallRng::from_rng(&mut rand::rng()),
+        }
+    }
+}
+
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct SpawnTimer {
+    pub since_last_spawn: f32,
+    #[serde(default)]
+    pub wave: usize,
+    #[serde(default)]
+    pub spawned: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Wave {
+    pub count: u32,
+    /// Seconds between two creeps of the wave
+    pub interval: f32,
+    /// Seconds before the first creep of the wave
+    #[serde(default)]
+    pub delay: f32,
+    pub health: f32,
+    pub speed: f32,
+    #[serde(default)]
+    pub bounty: i32,
+    /// Maximum relative deviation of the health of each creep, e.g. 0.1 for +-10%
+    #[serde(default)]
+    pub health_variance: f32,
+}
+
+/// When present, creeps are spawned following these waves instead of endlessly
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct Waves {
+    pub waves: Vec<Wave>,
+}
+
+impl Waves {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let waves = serde_json::from_str(&json).map_err(|err| err.to_string())?;
+        Ok(Self { waves })
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum GridShape {
+    #[default]
+    Square,
+    /// Pointy-top hexagons in axial coordinates, `y` growing towards the upper right
+    Hex,
+}
+
+/// How a world position falls into a square cell, hexagons always use the nearest one
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Rounding {
+    /// Cells are centered on their world position
+    #[default]
+    Nearest,
+    /// Cells extend from their world position towards positive x and y
+    Floor,
+}
+
+/// Conversions between grid cells and world positions, used by the game and every frontend
+#[derive(Resource, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct GridLayout {
+    /// Distance between the centers of two neighbouring cells
+    pub cell_size: f32,
+    /// World position of the cell (0, 0)
+    pub origin: Vec2,
+    pub rounding: Rounding,
+    /// Follows the active map
+    #[serde(default)]
+    pub shape: GridShape,
+}
+
+impl Default for GridLayout {
+    fn default() -> Self {
+        Self {
+            cell_size: 10.0,
+            origin: Vec2::ZERO,
+            rounding: Rounding::Nearest,
+            shape: GridShape::Square,
+        }
+    }
+}
+
+impl GridLayout {
+    pub fn grid_to_world(&self, cell: IVec2) -> Vec2 {
+        let cell = cell.as_vec2();
+        let offset = match self.shape {
+            GridShape::Square => cell,
+            GridShape::Hex => Vec2::new(cell.x + cell.y * 0.5, cell.y * HEX_ROW_HEIGHT),
+        };
+        self.origin + offset * self.cell_size
+    }
+
+    pub fn world_to_grid(&self, position: Vec2) -> IVec2 {
+        let cell = (position - self.origin) / self.cell_size;
+        match (self.shape, self.rounding) {
+            (GridShape::Square, Rounding::Nearest) => cell.round().as_ivec2(),
+            (GridShape::Square, Rounding::Floor) => cell.floor().as_ivec2(),
+            (GridShape::Hex, _) => {
+                let y = cell.y / HEX_ROW_HEIGHT;
+                round_axial(Vec2::new(cell.x - y * 0.5, y))
+            }
+        }
+    }
+
+    /// Average world position of several cells, e.g. the footprint of a turret
+    pub fn cells_center(&self, cells: &[IVec2]) -> Vec2 {
+        let sum: Vec2 = cells.iter().map(|cell| self.grid_to_world(*cell)).sum();
+        sum / cells.len().max(1) as f32
+    }
+
+    /// Cells of a line between two cells, each one touching the previous one
+    pub fn line(&self, from: IVec2, to: IVec2) -> Vec<IVec2> {
+        match self.shape {
+            GridShape::Square => {
+                let steps = (to - from).abs().max_element();
+                (0..=steps)
+                    .map(|step| {
+                        let t = step as f32 / steps.max(1) as f32;
+                        from.as_vec2().lerp(to.as_vec2(), t).round().as_ivec2()
+                    })
+                    .collect()
+            }
+            GridShape::Hex => {
+                let steps = crate::HexMap::distance(&from, &to);
+                (0..=steps)
+                    .map(|step| {
+                        let t = step as f32 / steps.max(1) as f32;
+                        // Nudged so that lines along hexagon edges pick a consistent side
+                        let nudge = Vec2::new(1e-3, 2e-3);
+                        round_axial(from.as_vec2().lerp(to.as_vec2(), t) + nudge)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Same layout with its origin moved by `offset`
+    pub fn translated(&self, offset: Vec2) -> Self {
+        Self {
+            origin: self.origin + offset,
+            ..*self
+        }
+    }
+
+    /// World area covered by a cell
+    pub fn cell_rect(&self, cell: IVec2) -> Rect {
+        let position = self.grid_to_world(cell);
+        match (self.shape, self.rounding) {
+            (GridShape::Square, Rounding::Nearest) => {
+                Rect::from_center_size(position, Vec2::splat(self.cell_size))
+            }
+            (GridShape::Square, Rounding::Floor) => {
+                Rect::from_corners(position, position + self.cell_size)
+            }
+            (GridShape::Hex, _) => {
+                Rect::from_center_size(position, Vec2::new(self.cell_size, self.hex_radius() * 2.0))
+            }
+        }
+    }
+
+    /// World area covered by a grid of the given size
+    pub fn grid_rect(&self, size: IVec2) -> Rect {
+        let last = size - IVec2::ONE;
+        self.cell_rect(IVec2::ZERO)
+            .union(self.cell_rect(last))
+            .union(self.cell_rect(IVec2::new(last.x, 0)))
+            .union(self.cell_rect(IVec2::new(0, last.y)))
+    }
+
+    /// Distance from the center of a hexagon to its corners
+    pub fn hex_radius(&self) -> f32 {
+        self.cell_size / 3.0_f32.sqrt()
+    }
+}
+
+/// Vertical distance between two rows of hexagons, relative to the cell size
+const HEX_ROW_HEIGHT: f32 = 0.866_025_4;
+
+/// Nearest hexagon of fractional axial coordinates
+pub(crate) fn round_axial(cell: Vec2) -> IVec2 {
+    let cube = Vec3::new(cell.x, cell.y, -cell.x - cell.y);
+    let rounded = cube.round();
+    let diff = (rounded - cube).abs();
+    if diff.x > diff.y && diff.x > diff.z {
+        IVec2::new((-rounded.y - rounded.z) as i32, rounded.y as i32)
+    } else if diff.y > diff.z {
+        IVec2::new(rounded.x as i32, (-rounded.x - rounded.z) as i32)
+    } else {
+        IVec2::new(rounded.x as i32, rounded.y as i32)
+    }
+}
+
+/// Creeps bucketed by position, rebuilt every tick so that turrets only look at the creeps around them
+#[derive(Resource)]
+pub struct CreepSpatialIndex {
+    bucket_size: f32,
+    buckets: HashMap<IVec2, Vec<(Entity, Vec2)>>,
+}
+
+impl Default for CreepSpatialIndex {
+    fn default() -> Self {
+        // About the range of a turret, range queries look at a few buckets
+        Self::new(25.0)
+    }
+}
+
+impl CreepSpatialIndex {
+    pub fn new(bucket_size: f32) -> Self {
+        Self {
+            bucket_size,
+            buckets: HashMap::default(),
+        }
+    }
+
+    /// Empty the buckets, keeping their memory for the next rebuild
+    pub fn clear(&mut self) {
+        self.buckets.values_mut().for_each(Vec::clear);
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec2) {
+        let bucket = self.bucket(position);
+        self.buckets
+            .entry(bucket)
+            .or_default()
+            .push((entity, position));
+    }
+
+    fn bucket(&self, position: Vec2) -> IVec2 {
+        (position / self.bucket_size).floor().as_ivec2()
+    }
+
+    /// Creeps at most `range` away from `center`, in a deterministic order
+    pub fn within(&self, center: Vec2, range: f32) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let min = self.bucket(center - range);
+        let max = self.bucket(center + range);
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|bucket| self.buckets.get(&bucket))
+            .flatten()
+            .copied()
+            .filter(move |(_, position)| center.distance(*position) <= range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_world_roundtrip() {
+        let layouts = [
+            GridLayout::default(),
+            GridLayout {
+                cell_size: 32.0,
+                origin: Vec2::new(-100.0, 50.0),
+                rounding: Rounding::Floor,
+                shape: GridShape::Square,
+            },
+        ];
+        for layout in layouts {
+            for x in -3..12 {
+                for y in -3..12 {
+                    let cell = IVec2::new(x, y);
+                    let rect = layout.cell_rect(cell);
+                    assert_eq!(layout.world_to_grid(layout.grid_to_world(cell)), cell);
+                    assert_eq!(layout.world_to_grid(rect.center()), cell);
+                    // Just inside the corners of the cell
+                    let margin = Vec2::splat(layout.cell_size * 0.01);
+                    assert_eq!(layout.world_to_grid(rect.min + margin), cell);
+                    assert_eq!(layout.world_to_grid(rect.max - margin), cell);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        let layout = GridLayout {
+            origin: Vec2::new(3.0, -7.0),
+            shape: GridShape::Hex,
+            ..default()
+        };
+        for x in -3..12 {
+            for y in -3..12 {
+                let cell = IVec2::new(x, y);
+                let center = layout.grid_to_world(cell);
+                assert_eq!(layout.world_to_grid(center), cell);
+                // Every neighbour is one cell away and points just inside the edges stay in the cell
+                for neighbour in crate::HexMap::neighbours(&cell) {
+                    let neighbour_center = layout.grid_to_world(neighbour);
+                    assert!((center.distance(neighbour_center) - layout.cell_size).abs() < 1e-3);
+                    let edge = center.lerp(neighbour_center, 0.49);
+                    assert_eq!(layout.world_to_grid(edge), cell);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let mut layout = GridLayout::default();
+        assert_eq!(
+            layout.line(IVec2::new(2, 2), IVec2::new(2, 2)),
+            vec![IVec2::new(2, 2)]
+        );
+        assert_eq!(layout.line(IVec2::new(0, 0), IVec2::new(3, 1)).len(), 4);
+        for shape in [GridShape::Square, GridShape::Hex] {
+            layout.shape = shape;
+            let line = layout.line(IVec2::new(1, 7), IVec2::new(8, 2));
+            assert_eq!(line.first(), Some(&IVec2::new(1, 7)));
+            assert_eq!(line.last(), Some(&IVec2::new(8, 2)));
+            for step in line.windows(2) {
+                let distance = match shape {
+                    GridShape::Square => (step[1] - step[0]).abs().max_element() as u32,
+                    GridShape::Hex => crate::HexMap::distance(&step[0], &step[1]),
+                };
+                assert_eq!(distance, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_rounding() {
+        let layout = GridLayout::default();
+        assert_eq!(
+            layout.world_to_grid(Vec2::new(14.9, -4.9)),
+            IVec2::new(1, 0)
+        );
+        assert_eq!(
+            layout.world_to_grid(Vec2::new(15.1, -5.1)),
+            IVec2::new(2, -1)
+        );
+        assert_eq!(
+            layout.grid_rect(IVec2::splat(10)),
+            Rect::new(-5.0, -5.0, 95.0, 95.0)
+        );
+    }
+
+    #[test]
+    fn spatial_index_matches_a_scan() {
+        let mut world = World::new();
+        let mut rng = SmallRng::seed_from_u64(0);
+        let creeps: Vec<(Entity, Vec2)> = (0..500)
+            .map(|_| {
+                let position = Vec2::new(
+                    rng.random_range(-50.0..150.0),
+                    rng.random_range(-50.0..150.0),
+                );
+                (world.spawn_empty().id(), position)
+            })
+            .collect();
+
+        let mut index = CreepSpatialIndex::default();
+        // Stale creeps of the previous tick are forgotten
+        index.insert(world.spawn_empty().id(), Vec2::ZERO);
+        index.clear();
+        for (entity, position) in &creeps {
+            index.insert(*entity, *position);
+        }
+
+        for _ in 0..50 {
+            let center = Vec2::new(rng.random_range(0.0..100.0), rng.random_range(0.0..100.0));
+            let range = rng.random_range(0.0..60.0);
+            let mut found: Vec<Entity> = index.within(center, range).map(|(e, _)| e).collect();
+            let mut expected: Vec<Entity> = creeps
+                .iter()
+                .filter(|(_, position)| center.distance(*position) <= range)
+                .map(|(e, _)| *e)
+                .collect();
+            found.sort();
+            expected.sort();
+            assert_eq!(found, expected);
+        }
+    }
+}
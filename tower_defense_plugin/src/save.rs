@@ -0,0 +1,609 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use rand::{RngCore, SeedableRng, rngs::SmallRng};
+use serde::{Deserialize, Serialize};
+
+use crate::components::*;
+use crate::config::GameConfig;
+use crate::events::{GameLoadedMessage, NewTurretMessage};
+use crate::resources::{CreepRng, GameData, GridLayout, SpawnTimer, TurretRng};
+use crate::{ActiveMap, MapFile};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TurretSnapshot {
+    pub turret: Turret,
+    pub strategy: Option<Strategy>,
+    pub bullet_thrower: Option<BulletThrower>,
+    pub projectile_launcher: Option<ProjectileLauncher>,
+    pub chain: Option<ChainTurret>,
+    pub mortar: Option<MortarTurret>,
+    pub aura: Option<AuraTurret>,
+    pub variance: Option<DamageVariance>,
+    pub base: BaseStats,
+    pub aim: Option<TurretAim>,
+    pub beam: Option<BeamSnapshot>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BeamSnapshot {
+    /// Index of the target in [`GameSnapshot::creeps`], `None` if the beam is not locked on a creep
+    pub target: Option<usize>,
+    pub time_on_target: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CreepSnapshot {
+    pub creep: Creep,
+    pub moving_entity: MovingEntity,
+    pub transform: Transform,
+    pub slowdown: Option<SlowDown>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BulletSnapshot {
+    pub direction: Vec2,
+    /// Index of the target in [`GameSnapshot::creeps`], `None` if the target is already gone
+    pub target: Option<usize>,
+    pub damage: f32,
+    pub speed: f32,
+    pub angular_velocity: f32,
+    pub is_crit: bool,
+    pub strategy: Strategy,
+    pub range: f32,
+    pub time_to_live: f32,
+    pub transform: Transform,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ShellSnapshot {
+    pub shell: MortarShell,
+    pub transform: Transform,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProjectileSnapshot {
+    pub velocity: Vec2,
+    /// Index of the target in [`GameSnapshot::creeps`], `None` if the target is already gone
+    pub target: Option<usize>,
+    pub damage: f32,
+    pub is_crit: bool,
+    pub time_to_live: f32,
+    pub transform: Transform,
+}
+
+/// Everything needed to restore a game in progress
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub map: MapFile,
+    pub game_data: GameData,
+    pub spawn_timer: SpawnTimer,
+    pub rng_seed: u64,
+    pub turret_rng_seed: u64,
+    pub turrets: Vec<TurretSnapshot>,
+    pub creeps: Vec<CreepSnapshot>,
+    pub bullets: Vec<BulletSnapshot>,
+    pub projectiles: Vec<ProjectileSnapshot>,
+    pub shells: Vec<ShellSnapshot>,
+}
+
+impl GameSnapshot {
+    pub fn capture(world: &mut World) -> Self {
+        // The RNG state can not be serialized, so reseed it from a value we can store
+        let rng_seed = {
+            let mut creep_rng = world.resource_mut::<CreepRng>();
+            let seed = creep_rng.rng.next_u64();
+            creep_rng.rng = SmallRng::seed_from_u64(seed);
+            seed
+        };
+        let turret_rng_seed = {
+            let mut turret_rng = world.resource_mut::<TurretRng>();
+            let seed = turret_rng.rng.next_u64();
+            turret_rng.rng = SmallRng::seed_from_u64(seed);
+            seed
+        };
+
+        let mut creep_entities = Vec::new();
+        let creeps = world
+            .query::<(Entity, &Creep, &MovingEntity, &Transform, Option<&SlowDown>)>()
+            .iter(world)
+            .map(|(entity, creep, moving_entity, transform, slowdown)| {
+                creep_entities.push(entity);
+                CreepSnapshot {
+                    creep: creep.clone(),
+                    moving_entity: moving_entity.clone(),
+                    transform: *transform,
+                    slowdown: slowdown.cloned(),
+                }
+            })
+            .collect();
+
+        let turrets = world
+            .query::<(
+                &Turret,
+                Option<&Strategy>,
+                Option<&BulletThrower>,
+                Option<&ProjectileLauncher>,
+                Option<&ChainTurret>,
+                Option<&MortarTurret>,
+                Option<&AuraTurret>,
+                &BaseStats,
+                Option<&DamageVariance>,
+                Option<&TurretAim>,
+                Option<&BeamTurret>,
+            )>()
+            .iter(world)
+            .map(
+                |(
+                    turret,
+                    strategy,
+                    bullet_thrower,
+                    projectile_launcher,
+                    chain,
+                    mortar,
+                    aura,
+                    base,
+                    variance,
+                    aim,
+                    beam,
+                )| {
+                    TurretSnapshot {
+                        turret: turret.clone(),
+                        strategy: strategy.copied(),
+                        bullet_thrower: bullet_thrower.cloned(),
+                        projectile_launcher: projectile_launcher.cloned(),
+                        chain: chain.cloned(),
+                        mortar: mortar.cloned(),
+                        aura: aura.cloned(),
+                        base: base.clone(),
+                        variance: variance.cloned(),
+                        aim: aim.cloned(),
+                        beam: beam.map(|beam| BeamSnapshot {
+                            target: beam.target.and_then(|target| {
+                                creep_entities.iter().position(|e| *e == target)
+                            }),
+                            time_on_target: beam.time_on_target,
+                        }),
+                    }
+                },
+            )
+            .collect();
+
+        let bullets = world
+            .query::<(&FollowerBullet, &Transform)>()
+            .iter(world)
+            .map(|(bullet, transform)| BulletSnapshot {
+                direction: bullet.direction,
+                target: creep_entities.iter().position(|e| *e == bullet.target),
+                damage: bullet.damage,
+                speed: bullet.speed,
+                angular_velocity: bullet.angular_velocity,
+                is_crit: bullet.is_crit,
+                strategy: bullet.strategy,
+                range: bullet.range,
+                time_to_live: bullet.time_to_live,
+                transform: *transform,
+            })
+            .collect();
+
+        let projectiles = world
+            .query::<(&Projectile, &Transform)>()
+            .iter(world)
+            .map(|(projectile, transform)| ProjectileSnapshot {
+                velocity: projectile.velocity,
+                target: creep_entities.iter().position(|e| *e == projectile.target),
+                damage: projectile.damage,
+                is_crit: projectile.is_crit,
+                time_to_live: projectile.time_to_live,
+                transform: *transform,
+            })
+            .collect();
+
+        let shells = world
+            .query::<(&MortarShell, &Transform)>()
+            .iter(world)
+            .map(|(shell, transform)| ShellSnapshot {
+                shell: shell.clone(),
+                transform: *transform,
+            })
+            .collect();
+
+        Self {
+            map: world.resource::<ActiveMap>().snapshot(),
+            game_data: world.resource::<GameData>().clone(),
+            spawn_timer: world.resource::<SpawnTimer>().clone(),
+            rng_seed,
+            turret_rng_seed,
+            turrets,
+            creeps,
+            bullets,
+            projectiles,
+            shells,
+        }
+    }
+
+    /// Replace the current game with the snapshot
+    pub fn restore(self, world: &mut World) {
+        clear_game(world);
+
+        insert_map(world, ActiveMap::from(self.map));
+        world.insert_resource(self.game_data);
+        world.insert_resource(self.spawn_timer);
+        world.insert_resource(CreepRng::from_seed(self.rng_seed));
+        world.insert_resource(TurretRng::from_seed(self.turret_rng_seed));
+
+        let creep_entities: Vec<Entity> = self
+            .creeps
+            .into_iter()
+            .map(|snapshot| {
+                let mut entity =
+                    world.spawn((snapshot.creep, snapshot.moving_entity, snapshot.transform));
+                if let Some(slowdown) = snapshot.slowdown {
+                    entity.insert(slowdown);
+                }
+                entity.id()
+            })
+            .collect();
+
+        for snapshot in self.turrets {
+            let turret_type = snapshot.turret.turret_type;
+            let position = snapshot.turret.position;
+
+            let mut entity = world.spawn((snapshot.base, snapshot.turret));
+            match turret_type {
+                TurretType::Basic => {
+                    entity.insert(BasicTurret {});
+                    if let Some(projectile_launcher) = snapshot.projectile_launcher {
+                        entity.insert(projectile_launcher);
+                    }
+                }
+                TurretType::Bomb => {
+                    entity.insert(BombTurret {});
+                }
+                TurretType::Follower => {
+                    entity.insert(
+                        snapshot
+                            .bullet_thrower
+                            .unwrap_or(BulletThrower { speed: 30.0 }),
+                    );
+                }
+                TurretType::Slow => {
+                    entity.insert(SlowTurret {});
+                }
+                TurretType::Beam => {
+                    let beam = snapshot
+                        .beam
+                        .map_or_else(BeamTurret::default, |beam| BeamTurret {
+                            target: beam.target.and_then(|i| creep_entities.get(i)).copied(),
+                            time_on_target: beam.time_on_target,
+                        });
+                    entity.insert(beam);
+                }
+                TurretType::Chain => {
+                    entity.insert(snapshot.chain.unwrap_or_default());
+                }
+                TurretType::Mortar => {
+                    entity.insert(snapshot.mortar.unwrap_or_default());
+                }
+                TurretType::Aura => {
+                    entity.insert(snapshot.aura.unwrap_or_default());
+                }
+                TurretType::Wall => {}
+            }
+            if let Some(strategy) = snapshot.strategy {
+                entity.insert(strategy);
+            }
+            if let Some(variance) = snapshot.variance {
+                entity.insert(variance);
+            }
+            if let Some(aim) = snapshot.aim {
+                entity.insert(aim);
+            }
+
+            world.write_message(NewTurretMessage {
+                turret_type,
+                position,
+            });
+        }
+
+        for snapshot in self.bullets {
+            // A bullet whose target is gone picks a new one on its next move
+            let target = snapshot
+                .target
+                .and_then(|i| creep_entities.get(i))
+                .copied()
+                .unwrap_or(Entity::PLACEHOLDER);
+            world.spawn((
+                FollowerBullet {
+                    direction: snapshot.direction,
+                    target,
+                    damage: snapshot.damage,
+                    speed: snapshot.speed,
+                    angular_velocity: snapshot.angular_velocity,
+                    is_crit: snapshot.is_crit,
+                    strategy: snapshot.strategy,
+                    range: snapshot.range,
+                    time_to_live: snapshot.time_to_live,
+                },
+                snapshot.transform,
+            ));
+        }
+
+        for snapshot in self.projectiles {
+            // Projectiles fly on without their target, until they expire
+            let target = snapshot
+                .target
+                .and_then(|i| creep_entities.get(i))
+                .copied()
+                .unwrap_or(Entity::PLACEHOLDER);
+            world.spawn((
+                Projectile {
+                    velocity: snapshot.velocity,
+                    target,
+                    damage: snapshot.damage,
+                    is_crit: snapshot.is_crit,
+                    time_to_live: snapshot.time_to_live,
+                },
+                snapshot.transform,
+            ));
+        }
+
+        for snapshot in self.shells {
+            world.spawn((snapshot.shell, snapshot.transform));
+        }
+
+        world.write_message(GameLoadedMessage);
+    }
+}
+
+fn clear_game(world: &mut World) {
+    let entities: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(
+            With<Turret>,
+            With<Creep>,
+            With<FollowerBullet>,
+            With<Projectile>,
+            With<MortarShell>,
+        )>>()
+        .iter(world)
+        .collect();
+    for entity in entities {
+        world.despawn(entity);
+    }
+}
+
+/// Command writing the current game to a JSON file
+pub struct SaveGame {
+    pub path: PathBuf,
+}
+
+impl SaveGame {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Command for SaveGame {
+    fn apply(self, world: &mut World) {
+        let snapshot = GameSnapshot::capture(world);
+        match serde_json::to_string(&snapshot) {
+            Ok(json) => match fs::write(&self.path, json) {
+                Ok(()) => println!("Game saved to {:?}", self.path),
+                Err(err) => println!("Could not write save file {:?}: {err}", self.path),
+            },
+            Err(err) => println!("Could not serialize game: {err}"),
+        }
+    }
+}
+
+/// Command replacing the current game with the one stored in a JSON file
+pub struct LoadGame {
+    pub path: PathBuf,
+}
+
+impl LoadGame {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Command for LoadGame {
+    fn apply(self, world: &mut World) {
+        let snapshot = fs::read_to_string(&self.path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| {
+                serde_json::from_str::<GameSnapshot>(&json).map_err(|err| err.to_string())
+            });
+        match snapshot {
+            Ok(snapshot) => {
+                snapshot.restore(world);
+                println!("Game loaded from {:?}", self.path);
+            }
+            Err(err) => println!("Could not load save file {:?}: {err}", self.path),
+        }
+    }
+}
+
+/// Replace the active map, the grid layout follows its shape
+fn insert_map(world: &mut World, map: ActiveMap) {
+    if let Some(mut layout) = world.get_resource_mut::<GridLayout>() {
+        layout.shape = map.shape();
+    }
+    world.insert_resource(map);
+}
+
+/// Command starting a new game on another map
+pub struct SwitchMap(pub ActiveMap);
+
+impl Command for SwitchMap {
+    fn apply(self, world: &mut World) {
+        clear_game(world);
+
+        let game_data = world
+            .get_resource::<GameConfig>()
+            .map(GameConfig::starting_game_data)
+            .unwrap_or_default();
+
+        insert_map(world, self.0);
+        world.insert_resource(game_data);
+        world.insert_resource(SpawnTimer::default());
+
+        world.write_message(GameLoadedMessage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FreeMap;
+    use bevy::math::ivec2;
+
+    fn new_world() -> World {
+        let mut world = World::new();
+        world.insert_resource(ActiveMap(Box::new(FreeMap::default())));
+        world.insert_resource(GameData::default());
+        world.insert_resource(SpawnTimer::default());
+        world.insert_resource(CreepRng::default());
+        world.insert_resource(TurretRng::default());
+        world.init_resource::<Messages<NewTurretMessage>>();
+        world.init_resource::<Messages<GameLoadedMessage>>();
+        world
+    }
+
+    #[test]
+    fn save_and_load_free_map() {
+        let mut world = new_world();
+        world
+            .resource_mut::<ActiveMap>()
+            .place_tower(&[ivec2(1, 1)]);
+        world.resource_mut::<GameData>().gold = 123;
+        world.resource_mut::<SpawnTimer>().since_last_spawn = 1.5;
+        world.spawn((
+            Turret {
+                turret_type: TurretType::Follower,
+                position: ivec2(1, 1),
+                transform: Transform::from_xyz(10.0, 10.0, 0.0),
+                range: 50.0,
+                damage: 10.0,
+                reload_time: 1.0,
+                last_fired: 0.5,
+            },
+            BaseStats {
+                range: 50.0,
+                damage: 10.0,
+                reload_time: 1.0,
+            },
+            BulletThrower { speed: 30.0 },
+            Strategy::Weakest,
+        ));
+        let creep = world
+            .spawn((
+                Creep {
+                    health: 42.0,
+                    max_health: 100.0,
+                    bounty: 5,
+                    wave: 0,
+                },
+                MovingEntity {
+                    waypoints: vec![Vec2::new(90.0, 90.0), Vec2::new(20.0, 10.0)],
+                    speed: 20.0,
+                },
+                Transform::from_xyz(12.0, 3.0, 0.0),
+                SlowDown {
+                    time_to_live: 2.0,
+                    strength: 5.0,
+                },
+            ))
+            .id();
+        world.spawn((
+            FollowerBullet {
+                direction: Vec2::X,
+                target: creep,
+                damage: 10.0,
+                is_crit: true,
+                speed: 30.0,
+                angular_velocity: 2.0,
+                strategy: Strategy::Weakest,
+                range: 50.0,
+                time_to_live: 3.0,
+            },
+            Transform::from_xyz(5.0, 5.0, 0.0),
+        ));
+
+        world.spawn((
+            Turret {
+                turret_type: TurretType::Beam,
+                position: ivec2(3, 3),
+                transform: Transform::from_xyz(30.0, 30.0, 0.0),
+                range: 35.0,
+                damage: 10.0,
+                reload_time: 0.0,
+                last_fired: 0.0,
+            },
+            BaseStats {
+                range: 35.0,
+                damage: 10.0,
+                reload_time: 0.0,
+            },
+            BeamTurret {
+                target: Some(creep),
+                time_on_target: 2.5,
+            },
+        ));
+
+        let snapshot = GameSnapshot::capture(&mut world);
+        let json = serde_json::to_string(&snapshot).unwrap();
+
+        let mut loaded = new_world();
+        loaded.insert_resource(ActiveMap::default());
+        serde_json::from_str::<GameSnapshot>(&json)
+            .unwrap()
+            .restore(&mut loaded);
+
+        assert!(loaded.resource::<ActiveMap>().as_dynamic().is_some());
+        assert_eq!(loaded.resource::<GameData>().gold, 123);
+        assert_eq!(loaded.resource::<SpawnTimer>().since_last_spawn, 1.5);
+        assert!(
+            !loaded
+                .resource::<ActiveMap>()
+                .is_turret_possible(&[ivec2(1, 1)])
+        );
+        assert_eq!(
+            loaded.resource::<ActiveMap>().get_path(),
+            world.resource::<ActiveMap>().get_path()
+        );
+
+        let (turret, strategy) = loaded
+            .query::<(&Turret, &Strategy)>()
+            .single(&loaded)
+            .unwrap();
+        assert_eq!(turret.last_fired, 0.5);
+        assert!(matches!(strategy, Strategy::Weakest));
+
+        let (creep, moving_entity, transform, slowdown) = loaded
+            .query::<(Entity, &MovingEntity, &Transform, &SlowDown)>()
+            .single(&loaded)
+            .unwrap();
+        assert_eq!(moving_entity.waypoints.len(), 2);
+        assert_eq!(transform.translation, Vec3::new(12.0, 3.0, 0.0));
+        assert_eq!(slowdown.time_to_live, 2.0);
+
+        let bullet = loaded.query::<&FollowerBullet>().single(&loaded).unwrap();
+        assert_eq!(bullet.target, creep);
+        assert!(matches!(bullet.strategy, Strategy::Weakest));
+        assert_eq!(bullet.time_to_live, 3.0);
+        assert!(bullet.is_crit);
+
+        // The beam keeps its target and its ramp
+        let beam = loaded.query::<&BeamTurret>().single(&loaded).unwrap();
+        assert_eq!(beam.target, Some(creep));
+        assert_eq!(beam.time_on_target, 2.5);
+
+        // Both games continue with the same random sequence
+        assert_eq!(
+            world.resource_mut::<CreepRng>().rng.next_u32(),
+            loaded.resource_mut::<CreepRng>().rng.next_u32()
+        );
+    }
+}
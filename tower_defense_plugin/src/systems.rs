@@ -1,15 +1,18 @@
 use std::f32;
 
+use bevy::ecs::query::QueryFilter;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::utils::Parallel;
 use bevy::{time::Time, transform::components::Transform};
 
+use crate::components::*;
+use crate::config::GameConfig;
 use crate::creep_tuple::CreepTuple;
 use crate::resources::*;
 use crate::top_n::TopN;
-use crate::utils::world_to_grid;
-use crate::{DynamicMap, events::*};
-use crate::{Map, components::*};
-use rand::RngCore;
+use crate::{ActiveMap, DynamicMap, events::*};
+use rand::{Rng, RngCore};
 
 pub fn setup() {}
 
@@ -40,20 +43,36 @@ pub fn move_creeps(
     }
 }
 
-pub fn update_creep_paths<T: Resource + DynamicMap>(
+/// Bucket the creeps once per tick for the range queries of the turrets
+pub fn update_creep_index(
+    mut index: ResMut<CreepSpatialIndex>,
+    creeps: Query<(Entity, &Transform), With<Creep>>,
+) {
+    index.clear();
+    for (entity, transform) in creeps.iter() {
+        index.insert(entity, transform.translation.truncate());
+    }
+}
+
+pub fn update_creep_paths(
     mut events: MessageReader<MapChangedMessage>,
     mut creeps: Query<(&Transform, &mut MovingEntity), With<Creep>>,
-    map: Res<T>,
+    map: Res<ActiveMap>,
+    layout: Res<GridLayout>,
+    config: Res<GameConfig>,
 ) {
+    // Creeps only need to be rerouted on maps where they find their own path
+    let Some(map) = map.as_dynamic() else {
+        events.clear();
+        return;
+    };
+
     for _event in events.read() {
         for (transform, mut moving_entity) in creeps.iter_mut() {
-            let start = world_to_grid(transform.translation);
+            let start = layout.world_to_grid(transform.translation.truncate());
             if let Some((new_path, _)) = map.compute_path(&start) {
-                let mut waypoints: Vec<Vec2> = new_path
-                    .iter()
-                    .map(|pos| Vec2::new(pos.x as f32 * 10.0, pos.y as f32 * 10.0))
-                    .rev()
-                    .collect();
+                let mut waypoints =
+                    creep_waypoints(&new_path, Some(map), &layout, config.smooth_paths);
                 waypoints.pop();
 
                 moving_entity.waypoints = waypoints;
@@ -64,30 +83,58 @@ pub fn update_creep_paths<T: Resource + DynamicMap>(
     }
 }
 
-pub fn handle_turret_placement<T>(
+/// World waypoints of a grid path, the last one first as expected by `MovingEntity`
+fn creep_waypoints(
+    path: &[IVec2],
+    map: Option<&dyn DynamicMap>,
+    layout: &GridLayout,
+    smooth: bool,
+) -> Vec<Vec2> {
+    let smoothed;
+    let path = match map {
+        Some(map) if smooth => {
+            smoothed = map.smooth_path(path);
+            &smoothed
+        }
+        _ => path,
+    };
+    path.iter()
+        .rev()
+        .map(|pos| layout.grid_to_world(*pos))
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_turret_placement(
     mut commands: Commands,
     mut events: MessageReader<PlaceTurretMessage>,
     mut game_data: ResMut<GameData>,
-    mut map: ResMut<T>,
+    mut map: ResMut<ActiveMap>,
     mut new_turret_writer: MessageWriter<NewTurretMessage>,
     mut map_changed_writer: MessageWriter<MapChangedMessage>,
-) where
-    T: Resource + Map,
-{
+    layout: Res<GridLayout>,
+    config: Res<GameConfig>,
+) {
     for event in events.read() {
         // Check the cost of the turret to ensure we can buy one
-        let (cost, range, reload_time) = match event.turret_type {
-            TurretType::Basic => (50, 25.0, 1.0),
-            TurretType::Bomb => (100, 20.0, 1.0),
-            TurretType::Follower => (75, 50.0, 1.0),
-            TurretType::Slow => (10, 50.0, 3.0),
-        };
+        let (cost, range, reload_time) = turret_stats(event.turret_type);
 
-        if game_data.gold >= cost && map.is_turret_possible(&event.position) {
+        let cells = event.turret_type.cells(event.position);
+        if game_data.gold >= cost && map.is_turret_possible(&cells) {
             // Deduct the cost of the turret from the player's gold
             game_data.gold -= cost;
 
-            create_turret(&mut commands, &mut map, event, range, reload_time);
+            // Place the base turret
+            map.place_tower(&cells);
+            create_turret(
+                &mut commands,
+                &layout,
+                event,
+                &cells,
+                range,
+                reload_time,
+                config.basic_projectile_speed,
+            );
 
             // Notify other systems that a new turret has been placed (e.g., for UI updates)
             new_turret_writer.write(NewTurretMessage {
@@ -104,38 +151,170 @@ pub fn handle_turret_placement<T>(
     }
 }
 
-fn create_turret<T>(
+/// Cost, range and reload time of each turret type
+fn turret_stats(turret_type: TurretType) -> (i32, f32, f32) {
+    match turret_type {
+        TurretType::Basic => (50, 25.0, 1.0),
+        TurretType::Bomb => (100, 20.0, 1.0),
+        TurretType::Follower => (75, 50.0, 1.0),
+        TurretType::Slow => (10, 50.0, 3.0),
+        // Beams deal damage every tick
+        TurretType::Beam => (120, 35.0, 0.0),
+        TurretType::Chain => (150, 30.0, 1.5),
+        TurretType::Mortar => (200, 80.0, 3.0),
+        TurretType::Aura => (80, 0.0, 0.0),
+        TurretType::Wall => (5, 0.0, 0.0),
+    }
+}
+
+/// Recomputes the effective stats of every turret from its base stats and the auras around it
+pub fn apply_auras(
+    mut events: MessageReader<NewTurretMessage>,
+    mut removed: RemovedComponents<Turret>,
+    layout: Res<GridLayout>,
+    mut turrets: Query<(Entity, &mut Turret, &BaseStats, Option<&AuraTurret>)>,
+) {
+    let placed = events.read().count() > 0;
+    let sold = removed.read().count() > 0;
+    if !placed && !sold {
+        return;
+    }
+
+    let auras: Vec<(Entity, Vec2, AuraTurret)> = turrets
+        .iter()
+        .filter_map(|(entity, turret, _, aura)| {
+            Some((
+                entity,
+                turret.transform.translation.truncate(),
+                aura?.clone(),
+            ))
+        })
+        .collect();
+
+    for (entity, mut turret, base, _) in &mut turrets {
+        let position = turret.transform.translation.truncate();
+        let (mut range, mut damage, mut fire_rate) = (1.0, 1.0, 1.0);
+        for (aura_entity, aura_position, aura) in &auras {
+            if *aura_entity != entity
+                && aura_position.distance(position) <= aura.cells * layout.cell_size
+            {
+                range += aura.range;
+                damage += aura.damage;
+                fire_rate += aura.fire_rate;
+            }
+        }
+        turret.range = base.range * range;
+        turret.damage = base.damage * damage;
+        turret.reload_time = base.reload_time / fire_rate;
+    }
+}
+
+/// Spread and critical hits of each turret type, `None` for flat damage
+fn damage_variance(turret_type: TurretType) -> Option<DamageVariance> {
+    let (spread, crit_chance, crit_multiplier) = match turret_type {
+        TurretType::Basic | TurretType::Follower => (0.1, 0.1, 2.0),
+        TurretType::Bomb => (0.2, 0.0, 1.0),
+        TurretType::Chain => (0.2, 0.05, 2.0),
+        TurretType::Mortar => (0.2, 0.15, 2.5),
+        // Beams deal their damage a bit every tick
+        TurretType::Slow | TurretType::Beam | TurretType::Aura | TurretType::Wall => {
+            return None;
+        }
+    };
+    Some(DamageVariance {
+        spread,
+        crit_chance,
+        crit_multiplier,
+    })
+}
+
+pub fn handle_wall_placement(
+    mut commands: Commands,
+    mut events: MessageReader<PlaceWallsMessage>,
+    mut game_data: ResMut<GameData>,
+    mut map: ResMut<ActiveMap>,
+    mut new_turret_writer: MessageWriter<NewTurretMessage>,
+    mut map_changed_writer: MessageWriter<MapChangedMessage>,
+    layout: Res<GridLayout>,
+) {
+    for event in events.read() {
+        let (cost, range, reload_time) = turret_stats(TurretType::Wall);
+        let cells = layout.line(event.from, event.to);
+        let total_cost = cost * cells.len() as i32;
+
+        // The whole line is validated at once so that it never closes the path
+        if game_data.gold >= total_cost && map.is_turret_possible(&cells) {
+            game_data.gold -= total_cost;
+            map.place_tower(&cells);
+
+            for cell in &cells {
+                let wall = PlaceTurretMessage {
+                    turret_type: TurretType::Wall,
+                    position: *cell,
+                };
+                create_turret(
+                    &mut commands,
+                    &layout,
+                    &wall,
+                    &[*cell],
+                    range,
+                    reload_time,
+                    None,
+                );
+                new_turret_writer.write(NewTurretMessage {
+                    turret_type: TurretType::Wall,
+                    position: *cell,
+                });
+            }
+
+            map_changed_writer.write(MapChangedMessage {});
+
+            println!("{} walls placed successfully!", cells.len());
+        } else {
+            println!(
+                "Can not place walls from {:?} to {:?}!",
+                event.from, event.to
+            );
+        }
+    }
+}
+
+fn create_turret(
     commands: &mut Commands,
-    map: &mut ResMut<T>,
+    layout: &GridLayout,
     event: &PlaceTurretMessage,
+    cells: &[IVec2],
     range: f32,
     reload_time: f32,
-) where
-    T: Resource + Map,
-{
-    // Place the base turret
-    map.place_tower(&event.position);
-
-    let turret_id = commands
-        .spawn(Turret {
-            turret_type: event.turret_type,
-            position: event.position,
-            transform: Transform::from_xyz(
-                event.position.x as f32 * 10.0,
-                event.position.y as f32 * 10.0,
-                0.0,
-            ),
-            range,
-            damage: 10.0,
-            reload_time,
-            last_fired: 0.0,
-        })
-        .id();
+    projectile_speed: Option<f32>,
+) {
+    let turret = Turret {
+        turret_type: event.turret_type,
+        position: event.position,
+        // Ranges are measured from the center of the footprint
+        transform: Transform::from_translation(layout.cells_center(cells).extend(0.0)),
+        range,
+        damage: 10.0,
+        reload_time,
+        last_fired: 0.0,
+    };
+    let turret_id = commands.spawn((BaseStats::from(&turret), turret)).id();
+    if let Some(variance) = damage_variance(event.turret_type) {
+        commands.entity(turret_id).insert(variance);
+    }
+    if let Some(turn_rate) = event.turret_type.turn_rate() {
+        commands.entity(turret_id).insert(TurretAim::new(turn_rate));
+    }
 
     // Place the specific turret type (which will handle the actual shooting)
     match event.turret_type {
         TurretType::Basic => {
             commands.entity(turret_id).insert(BasicTurret {});
+            if let Some(speed) = projectile_speed {
+                commands
+                    .entity(turret_id)
+                    .insert(ProjectileLauncher { speed });
+            }
         }
         TurretType::Bomb => {
             commands.entity(turret_id).insert(BombTurret {});
@@ -148,186 +327,943 @@ fn create_turret<T>(
         TurretType::Slow => {
             commands.entity(turret_id).insert(SlowTurret {});
         }
+        TurretType::Beam => {
+            commands.entity(turret_id).insert(BeamTurret::default());
+        }
+        TurretType::Chain => {
+            commands.entity(turret_id).insert(ChainTurret::default());
+        }
+        TurretType::Mortar => {
+            commands.entity(turret_id).insert(MortarTurret::default());
+        }
+        TurretType::Aura => {
+            commands.entity(turret_id).insert(AuraTurret::default());
+        }
+        TurretType::Wall => {}
     }
 }
 
-pub fn spawn_creeps<T>(
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_creeps(
     mut commands: Commands,
     time: Res<Time>,
-    mut last_spawn_time: Local<f32>,
-    map: Res<T>,
+    mut spawn_timer: ResMut<SpawnTimer>,
+    waves: Option<Res<Waves>>,
+    map: Res<ActiveMap>,
     mut rng: ResMut<CreepRng>,
-) where
-    T: Resource + Map,
-{
-    *last_spawn_time += time.delta_secs();
+    config: Res<GameConfig>,
+    layout: Res<GridLayout>,
+) {
+    spawn_timer.since_last_spawn += time.delta_secs();
 
-    if *last_spawn_time > 2.0 {
-        *last_spawn_time = 0.0;
+    let next_creep = match waves {
+        Some(waves) => next_wave_creep(&mut spawn_timer, &waves, &mut rng),
+        None => next_endless_creep(&mut spawn_timer, &mut rng),
+    };
 
-        let start_pos = map.get_start();
-        let waypoints: Vec<Vec2> = map
-            .get_path()
-            .iter()
-            .map(|pos| Vec2::new(pos.x as f32 * 10.0, pos.y as f32 * 10.0))
-            .rev()
-            .collect();
+    if let Some((mut creep, mut speed)) = next_creep {
+        let difficulty = &config.difficulty;
+        creep.health *= difficulty.creep_health;
+        creep.max_health *= difficulty.creep_health;
+        creep.bounty = (creep.bounty as f32 * difficulty.creep_bounty).round() as i32;
+        speed *= difficulty.creep_speed;
 
-        let (speed, health) = if rng.rng.next_u32() < u32::MAX / 4 {
-            (35.0, 50.0)
-        } else {
-            (20.0, 100.0)
-        };
+        let start_pos = map.get_start();
+        let waypoints = creep_waypoints(
+            map.get_path(),
+            map.as_dynamic(),
+            &layout,
+            config.smooth_paths,
+        );
 
         commands.spawn((
             MovingEntity { speed, waypoints },
-            Transform::from_translation(Vec3::new(
-                start_pos.x as f32 * 10.0,
-                start_pos.y as f32 * 10.0,
-                0.0,
-            )),
-            Creep {
-                health,
-                max_health: 100.0,
-            },
+            Transform::from_translation(layout.grid_to_world(start_pos).extend(0.0)),
+            creep,
         ));
     }
 }
 
-macro_rules! shoot_n_creeps {
-    ($turret: ident, $creeps: ident, $time: ident, $strategy: ident, $n_creeps: literal, $inner_function: expr) => {
-        if time_to_fire(&mut $turret, &$time) {
-            let turret_position = $turret.transform.translation.truncate();
-            let mut ro_creeps =
-                $creeps.transmute_lens::<(Entity, &Creep, &Transform, &MovingEntity)>();
-            let n_creeps = find_top_creeps_within_range(
+fn next_endless_creep(spawn_timer: &mut SpawnTimer, rng: &mut CreepRng) -> Option<(Creep, f32)> {
+    if spawn_timer.since_last_spawn <= 2.0 {
+        return None;
+    }
+    spawn_timer.since_last_spawn = 0.0;
+
+    let (speed, health) = if rng.rng.next_u32() < u32::MAX / 4 {
+        (35.0, 50.0)
+    } else {
+        (20.0, 100.0)
+    };
+
+    Some((
+        Creep {
+            health,
+            max_health: 100.0,
+            bounty: 5,
+            wave: 0,
+        },
+        speed,
+    ))
+}
+
+fn next_wave_creep(
+    spawn_timer: &mut SpawnTimer,
+    waves: &Waves,
+    rng: &mut CreepRng,
+) -> Option<(Creep, f32)> {
+    let wave = waves.waves.get(spawn_timer.wave)?;
+
+    let wait = if spawn_timer.spawned == 0 {
+        wave.delay
+    } else {
+        wave.interval
+    };
+    if spawn_timer.since_last_spawn < wait {
+        return None;
+    }
+    spawn_timer.since_last_spawn = 0.0;
+
+    let creep_wave = spawn_timer.wave;
+    spawn_timer.spawned += 1;
+    if spawn_timer.spawned >= wave.count {
+        spawn_timer.wave += 1;
+        spawn_timer.spawned = 0;
+    }
+    if wave.count == 0 {
+        return None;
+    }
+
+    let health = wave.health * (1.0 + wave.health_variance * rng.rng.random_range(-1.0..=1.0));
+    Some((
+        Creep {
+            health,
+            max_health: health,
+            bounty: wave.bounty,
+            wave: creep_wave,
+        },
+        wave.speed,
+    ))
+}
+
+/// Largest angle in radians between a turret and its target for it to fire
+pub const AIM_TOLERANCE: f32 = 0.05;
+
+/// Turrets turn towards the target they would fire at, no faster than their turn rate
+pub fn aim_turrets(
+    time: Res<Time>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(
+        &Turret,
+        &mut TurretAim,
+        Option<&Strategy>,
+        Option<&MortarTurret>,
+    )>,
+    creeps: Query<(Entity, &Creep, &Transform, &MovingEntity)>,
+) {
+    turrets
+        .par_iter_mut()
+        .for_each(|(turret, mut aim, strategy, mortar)| {
+            let turret_position = turret.transform.translation.truncate();
+            let min_range = mortar.map_or(0.0, |mortar| mortar.min_range);
+            let Some(&(_, target_position, _)) = find_top_creeps_within_range(
                 turret_position,
-                $turret.range,
-                &ro_creeps.query(),
-                $strategy,
-                $n_creeps,
-            );
+                min_range,
+                turret.range,
+                &index,
+                &creeps,
+                strategy,
+                1,
+            )
+            .first() else {
+                aim.aligned = false;
+                return;
+            };
 
-            if !n_creeps.is_empty() {
-                for (creep_entity, creep_position, turret_position) in n_creeps {
-                    $inner_function(creep_entity, creep_position, turret_position);
-                }
+            let to_target = target_position - turret_position;
+            let wanted = to_target.y.atan2(to_target.x);
+            let step = aim.turn_rate * time.delta_secs();
+            let diff = wrap_angle(wanted - aim.angle);
+            let turned = diff.clamp(-step, step);
+            aim.angle = wrap_angle(aim.angle + turned);
+            aim.aligned = (diff - turned).abs() <= AIM_TOLERANCE;
+        });
+}
+
+/// Brings an angle back within [-PI, PI)
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + f32::consts::PI).rem_euclid(f32::consts::TAU) - f32::consts::PI
+}
+
+/// Shot chosen by a turret, applied once every turret has picked its targets
+#[derive(Clone, Copy)]
+pub struct FireIntent {
+    turret: Entity,
+    target: Entity,
+    target_position: Vec2,
+    turret_position: Vec2,
+}
+
+/// Turrets ready to fire pick their `n` targets in parallel, all from the same state of the creeps.
+/// Creeps closer to a turret than its `min_range` are out of its reach
+#[allow(clippy::too_many_arguments)]
+fn fire_intents<T: QueryFilter, C: QueryFilter>(
+    time: &Time,
+    index: &CreepSpatialIndex,
+    turrets: &mut Query<(Entity, &mut Turret, Option<&Strategy>), T>,
+    aims: &Query<&TurretAim>,
+    creeps: &Query<(Entity, &Creep, &Transform, &MovingEntity), C>,
+    min_range: impl Fn(Entity) -> f32 + Sync,
+    n: usize,
+    queue: &mut Parallel<Vec<FireIntent>>,
+) -> Vec<FireIntent> {
+    turrets
+        .par_iter_mut()
+        .for_each(|(turret_entity, mut turret, strategy)| {
+            if time_to_fire(&mut turret, time) && is_aligned(aims, turret_entity) {
+                let turret_position = turret.transform.translation.truncate();
+                let targets = find_top_creeps_within_range(
+                    turret_position,
+                    min_range(turret_entity),
+                    turret.range,
+                    index,
+                    creeps,
+                    strategy,
+                    n,
+                );
 
-                $turret.last_fired = 0.0;
+                if !targets.is_empty() {
+                    queue.scope(|intents| {
+                        intents.extend(targets.into_iter().map(
+                            |(target, target_position, turret_position)| FireIntent {
+                                turret: turret_entity,
+                                target,
+                                target_position,
+                                turret_position,
+                            },
+                        ))
+                    });
+                    turret.last_fired = 0.0;
+                }
             }
-        }
-    };
+        });
+
+    let mut intents = Vec::new();
+    queue.drain_into(&mut intents);
+    // Threads fill the queue in any order, the stable sort keeps the order of each turret targets
+    intents.sort_by_key(|intent| intent.turret);
+    intents
 }
 
+/// Basic turrets hitting their target instantly
+type HitscanTurret = (With<BasicTurret>, Without<ProjectileLauncher>);
+
+#[allow(clippy::too_many_arguments)]
 pub fn basic_turret_system(
     time: Res<Time>,
-    mut turrets: Query<(&mut Turret, Option<&Strategy>), With<BasicTurret>>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(Entity, &mut Turret, Option<&Strategy>), HitscanTurret>,
+    aims: Query<&TurretAim>,
     mut creeps: Query<(Entity, &mut Creep, &Transform, &MovingEntity)>,
     mut fire_events: MessageWriter<BasicFireMessage>,
+    mut damage_events: MessageWriter<CreepDamagedMessage>,
+    mut rolls: DamageRolls,
+    mut queue: Local<Parallel<Vec<FireIntent>>>,
 ) {
-    for (mut turret, strategy) in turrets.iter_mut() {
-        shoot_n_creeps!(
-            turret,
-            creeps,
-            time,
-            strategy,
-            1,
-            |creep_entity, creep_position, _turret_entity| {
-                if let Ok((_, mut creep, _, _)) = creeps.get_mut(creep_entity) {
-                    shoot_creep(&mut fire_events, &turret, &mut creep, creep_position);
-                }
-            }
-        );
+    let intents = fire_intents(
+        &time,
+        &index,
+        &mut turrets,
+        &aims,
+        &creeps.as_readonly(),
+        |_| 0.0,
+        1,
+        &mut queue,
+    );
+
+    for intent in intents {
+        if let Ok((_, turret, _)) = turrets.get(intent.turret)
+            && let Ok((_, mut creep, _, _)) = creeps.get_mut(intent.target)
+        {
+            let (damage, is_crit) = rolls.roll(intent.turret, turret.damage);
+            damage_creep(&mut damage_events, turret.turret_type, &mut creep, damage);
+            fire_events.write(BasicFireMessage {
+                origin: intent.turret_position,
+                target: intent.target_position,
+                is_crit,
+            });
+        }
     }
 }
 
-pub fn slow_turret_system(
+/// Basic turrets with a launcher aim where their target will be when the projectile arrives
+#[allow(clippy::too_many_arguments)]
+pub fn projectile_turret_system(
     time: Res<Time>,
-    mut turrets: Query<(&mut Turret, Option<&Strategy>), With<SlowTurret>>,
-    mut creeps: Query<(Entity, &Creep, &Transform, &MovingEntity), Without<SlowDown>>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(Entity, &mut Turret, Option<&Strategy>), With<ProjectileLauncher>>,
+    aims: Query<&TurretAim>,
+    launchers: Query<&ProjectileLauncher>,
+    creeps: Query<(Entity, &Creep, &Transform, &MovingEntity)>,
+    slowed: Query<&SlowDown>,
     mut commands: Commands,
+    mut rolls: DamageRolls,
+    mut queue: Local<Parallel<Vec<FireIntent>>>,
 ) {
-    for (mut turret, strategy) in turrets.iter_mut() {
-        shoot_n_creeps!(
-            turret,
-            creeps,
-            time,
-            strategy,
-            1,
-            |creep_entity, _creep_position, _turret_entity| {
-                commands.entity(creep_entity).insert_if_new(SlowDown {
-                    time_to_live: 5.0,
-                    strength: 5.0,
+    for intent in fire_intents(
+        &time,
+        &index,
+        &mut turrets,
+        &aims,
+        &creeps,
+        |_| 0.0,
+        1,
+        &mut queue,
+    ) {
+        if let Ok((_, turret, _)) = turrets.get(intent.turret)
+            && let Ok(launcher) = launchers.get(intent.turret)
+            && let Ok((_, _, _, moving_entity)) = creeps.get(intent.target)
+        {
+            let slowdown = slowed.get(intent.target).ok();
+            let velocity = creep_velocity(intent.target_position, moving_entity, slowdown);
+            let aim = intercept(
+                intent.turret_position,
+                launcher.speed,
+                intent.target_position,
+                velocity,
+            )
+            .unwrap_or(intent.target_position);
+
+            let (damage, is_crit) = rolls.roll(intent.turret, turret.damage);
+            commands.spawn((
+                Projectile {
+                    velocity: (aim - intent.turret_position).normalize_or_zero() * launcher.speed,
+                    target: intent.target,
+                    damage,
+                    is_crit,
+                    // Long enough to reach a target leaving the range
+                    time_to_live: 1.5 * turret.range / launcher.speed,
+                },
+                Transform::from_translation(turret.transform.translation),
+            ));
+        }
+    }
+}
+
+/// Current velocity of a creep heading to its next waypoint
+fn creep_velocity(
+    position: Vec2,
+    moving_entity: &MovingEntity,
+    slowdown: Option<&SlowDown>,
+) -> Vec2 {
+    let Some(waypoint) = moving_entity.waypoints.last() else {
+        return Vec2::ZERO;
+    };
+    let mut speed = moving_entity.speed;
+    if let Some(slowdown) = slowdown {
+        speed /= slowdown.strength;
+    }
+    (*waypoint - position).normalize_or_zero() * speed
+}
+
+/// Point where a projectile fired at `speed` meets a target keeping its velocity
+fn intercept(origin: Vec2, speed: f32, target: Vec2, velocity: Vec2) -> Option<Vec2> {
+    // |offset + velocity * t| = speed * t
+    let offset = target - origin;
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+
+    let time = if a.abs() < f32::EPSILON {
+        (b < 0.0).then(|| -c / b)?
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+            .into_iter()
+            .filter(|t| *t >= 0.0)
+            .reduce(f32::min)?
+    };
+    Some(target + velocity * time)
+}
+
+const PROJECTILE_HIT_RADIUS: f32 = 4.0;
+
+#[allow(clippy::too_many_arguments)]
+pub fn move_projectiles(
+    mut commands: Commands,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Transform), Without<Creep>>,
+    mut creeps: Query<(&Transform, &mut Creep)>,
+    mut damage_events: MessageWriter<CreepDamagedMessage>,
+    mut hit_events: MessageWriter<BulletHitMessage>,
+    time: Res<Time>,
+    mut hits: Local<Parallel<Vec<BulletHit>>>,
+    mut expired: Local<Parallel<Vec<Entity>>>,
+) {
+    let readonly_creeps = creeps.as_readonly();
+    projectiles
+        .par_iter_mut()
+        .for_each(|(entity, mut projectile, mut transform)| {
+            let position =
+                transform.translation.truncate() + projectile.velocity * time.delta_secs();
+            transform.translation = position.extend(transform.translation.z);
+            projectile.time_to_live -= time.delta_secs();
+
+            let target_position = readonly_creeps
+                .get(projectile.target)
+                .map(|(target, _)| target.translation.truncate())
+                .ok()
+                .filter(|target| target.distance(position) < PROJECTILE_HIT_RADIUS);
+            if let Some(target_position) = target_position {
+                hits.scope(|hits| {
+                    hits.push(BulletHit {
+                        bullet: entity,
+                        target: projectile.target,
+                        target_position,
+                        damage: projectile.damage,
+                        is_crit: projectile.is_crit,
+                    })
                 });
+            } else if projectile.time_to_live <= 0.0 {
+                expired.scope(|expired| expired.push(entity));
             }
-        );
+        });
+
+    let mut landed = Vec::new();
+    hits.drain_into(&mut landed);
+    landed.sort_by_key(|hit| hit.bullet);
+    for hit in landed {
+        // A target killed by another shot this tick is missed
+        if let Ok((_, mut creep)) = creeps.get_mut(hit.target)
+            && creep.health > 0.0
+        {
+            damage_creep(
+                &mut damage_events,
+                TurretType::Basic,
+                &mut creep,
+                hit.damage,
+            );
+            hit_events.write(BulletHitMessage {
+                target: hit.target_position,
+                is_crit: hit.is_crit,
+            });
+            commands.entity(hit.bullet).despawn();
+        }
+    }
+    for entity in expired.drain() {
+        commands.entity(entity).despawn();
     }
 }
 
+pub fn slow_turret_system(
+    time: Res<Time>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(Entity, &mut Turret, Option<&Strategy>), With<SlowTurret>>,
+    aims: Query<&TurretAim>,
+    creeps: Query<(Entity, &Creep, &Transform, &MovingEntity), Without<SlowDown>>,
+    mut commands: Commands,
+    mut queue: Local<Parallel<Vec<FireIntent>>>,
+) {
+    for intent in fire_intents(
+        &time,
+        &index,
+        &mut turrets,
+        &aims,
+        &creeps,
+        |_| 0.0,
+        1,
+        &mut queue,
+    ) {
+        commands.entity(intent.target).insert_if_new(SlowDown {
+            time_to_live: 5.0,
+            strength: 5.0,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn bomb_turret_system(
     time: Res<Time>,
-    mut turrets: Query<&mut Turret, With<BombTurret>>,
-    mut creeps: Query<(Entity, &mut Creep, &Transform)>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(Entity, &mut Turret), With<BombTurret>>,
+    mut creeps: Query<&mut Creep>,
     mut fire_events: MessageWriter<BasicFireMessage>,
+    mut damage_events: MessageWriter<CreepDamagedMessage>,
+    mut rolls: DamageRolls,
+    mut queue: Local<Parallel<Vec<Entity>>>,
 ) {
-    for mut turret in turrets.iter_mut() {
-        if time_to_fire(&mut turret, &time) {
-            let turret_position = turret.transform.translation.truncate();
-            for (_, mut creep, creep_position) in creeps.iter_mut() {
-                let creep_position = creep_position.translation.truncate();
-                if turret_position.distance(creep_position) <= turret.range {
-                    shoot_creep(&mut fire_events, &turret, &mut creep, creep_position);
+    turrets
+        .par_iter_mut()
+        .for_each(|(turret_entity, mut turret)| {
+            if time_to_fire(&mut turret, &time) {
+                let turret_position = turret.transform.translation.truncate();
+                if index.within(turret_position, turret.range).next().is_some() {
+                    queue.scope(|fired| fired.push(turret_entity));
                     turret.last_fired = 0.0;
                 }
             }
+        });
+
+    let mut fired = Vec::new();
+    queue.drain_into(&mut fired);
+    fired.sort();
+    for turret_entity in fired {
+        if let Ok((_, turret)) = turrets.get(turret_entity) {
+            let origin = turret.transform.translation.truncate();
+            let (damage, is_crit) = rolls.roll(turret_entity, turret.damage);
+            let hits = splash_damage(
+                &mut damage_events,
+                &index,
+                &mut creeps,
+                TurretType::Bomb,
+                origin,
+                turret.range,
+                damage,
+            );
+            for target in hits {
+                fire_events.write(BasicFireMessage {
+                    origin,
+                    target,
+                    is_crit,
+                });
+            }
         }
     }
 }
 
-fn shoot_creep(
-    fire_events: &mut MessageWriter<BasicFireMessage>,
-    turret: &Turret,
-    creep: &mut Creep,
-    creep_position: Vec2,
+#[allow(clippy::too_many_arguments)]
+pub fn mortar_turret_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(Entity, &mut Turret, Option<&Strategy>), With<MortarTurret>>,
+    mortars: Query<&MortarTurret>,
+    aims: Query<&TurretAim>,
+    creeps: Query<(Entity, &Creep, &Transform, &MovingEntity)>,
+    slowdowns: Query<&SlowDown>,
+    mut fire_events: MessageWriter<BasicFireMessage>,
+    mut rolls: DamageRolls,
+    mut queue: Local<Parallel<Vec<FireIntent>>>,
+) {
+    let intents = fire_intents(
+        &time,
+        &index,
+        &mut turrets,
+        &aims,
+        &creeps,
+        |turret| mortars.get(turret).map_or(0.0, |mortar| mortar.min_range),
+        1,
+        &mut queue,
+    );
+
+    for intent in intents {
+        if let Ok((_, turret, _)) = turrets.get(intent.turret)
+            && let Ok(mortar) = mortars.get(intent.turret)
+            && let Ok((_, _, _, moving_entity)) = creeps.get(intent.target)
+        {
+            let origin = intent.turret_position;
+            let impact = predict_position(
+                intent.target_position,
+                moving_entity,
+                slowdowns.get(intent.target).ok(),
+                mortar.flight_time,
+            );
+            let (damage, is_crit) = rolls.roll(intent.turret, turret.damage);
+            commands.spawn((
+                MortarShell {
+                    target: impact,
+                    velocity: (impact - origin) / mortar.flight_time,
+                    time_to_impact: mortar.flight_time,
+                    radius: mortar.splash_radius,
+                    damage,
+                },
+                Transform::from_translation(turret.transform.translation),
+            ));
+            fire_events.write(BasicFireMessage {
+                origin,
+                target: impact,
+                is_crit,
+            });
+        }
+    }
+}
+
+/// Where a creep will be after `time` following its waypoints
+fn predict_position(
+    position: Vec2,
+    moving_entity: &MovingEntity,
+    slowdown: Option<&SlowDown>,
+    time: f32,
+) -> Vec2 {
+    let mut distance = creep_velocity(position, moving_entity, slowdown).length() * time;
+    let mut position = position;
+    for waypoint in moving_entity.waypoints.iter().rev() {
+        let step = position.distance(*waypoint);
+        if distance < step {
+            return position.move_towards(*waypoint, distance);
+        }
+        distance -= step;
+        position = *waypoint;
+    }
+    position
+}
+
+pub fn move_mortar_shells(
+    mut commands: Commands,
+    time: Res<Time>,
+    index: Res<CreepSpatialIndex>,
+    mut shells: Query<(Entity, &mut MortarShell, &mut Transform)>,
+    mut creeps: Query<&mut Creep>,
+    mut damage_events: MessageWriter<CreepDamagedMessage>,
 ) {
-    creep.health -= turret.damage;
+    let mut landed = Vec::new();
+    for (entity, mut shell, mut transform) in &mut shells {
+        shell.time_to_impact -= time.delta_secs();
+        if shell.time_to_impact <= 0.0 {
+            landed.push(entity);
+        } else {
+            transform.translation += (shell.velocity * time.delta_secs()).extend(0.0);
+        }
+    }
 
-    fire_events.write(BasicFireMessage {
-        origin: turret.position,
-        target: creep_position,
-    });
+    landed.sort();
+    for entity in landed {
+        if let Ok((_, shell, _)) = shells.get(entity) {
+            splash_damage(
+                &mut damage_events,
+                &index,
+                &mut creeps,
+                TurretType::Mortar,
+                shell.target,
+                shell.radius,
+                shell.damage,
+            );
+        }
+        commands.entity(entity).despawn();
+    }
 }
 
+/// Damages every creep within `radius` of `center`, returns where they were hit
+fn splash_damage(
+    damage_events: &mut MessageWriter<CreepDamagedMessage>,
+    index: &CreepSpatialIndex,
+    creeps: &mut Query<&mut Creep>,
+    turret_type: TurretType,
+    center: Vec2,
+    radius: f32,
+    damage: f32,
+) -> Vec<Vec2> {
+    let mut hits = Vec::new();
+    for (target, target_position) in index.within(center, radius) {
+        if let Ok(mut creep) = creeps.get_mut(target) {
+            damage_creep(damage_events, turret_type, &mut creep, damage);
+            hits.push(target_position);
+        }
+    }
+    hits
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn chain_turret_system(
+    time: Res<Time>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(Entity, &mut Turret, Option<&Strategy>), With<ChainTurret>>,
+    aims: Query<&TurretAim>,
+    chains: Query<&ChainTurret>,
+    mut creeps: Query<(Entity, &mut Creep, &Transform, &MovingEntity)>,
+    mut fire_events: MessageWriter<BasicFireMessage>,
+    mut damage_events: MessageWriter<CreepDamagedMessage>,
+    mut rolls: DamageRolls,
+    mut queue: Local<Parallel<Vec<FireIntent>>>,
+) {
+    let intents = fire_intents(
+        &time,
+        &index,
+        &mut turrets,
+        &aims,
+        &creeps.as_readonly(),
+        |_| 0.0,
+        1,
+        &mut queue,
+    );
+
+    for intent in intents {
+        let (Ok((_, turret, strategy)), Ok(chain)) =
+            (turrets.get(intent.turret), chains.get(intent.turret))
+        else {
+            continue;
+        };
+
+        let mut hit = vec![intent.target];
+        let mut origin = intent.turret_position;
+        let mut target_position = intent.target_position;
+        // Every bounce shares the roll of the shot
+        let (mut damage, is_crit) = rolls.roll(intent.turret, turret.damage);
+        loop {
+            let target = *hit.last().unwrap();
+            if let Ok((_, mut creep, _, _)) = creeps.get_mut(target) {
+                damage_creep(&mut damage_events, TurretType::Chain, &mut creep, damage);
+            }
+            fire_events.write(BasicFireMessage {
+                origin,
+                target: target_position,
+                is_crit,
+            });
+            if hit.len() > chain.bounces {
+                break;
+            }
+
+            // The candidates picked by the turret strategy may all have been hit already
+            let creeps = creeps.as_readonly();
+            let next = find_top_creeps_within_range(
+                target_position,
+                0.0,
+                chain.bounce_radius,
+                &index,
+                &creeps,
+                strategy,
+                hit.len() + 1,
+            )
+            .into_iter()
+            .filter(|(creep, _, _)| {
+                !hit.contains(creep)
+                    && creeps
+                        .get(*creep)
+                        .is_ok_and(|(_, creep, _, _)| creep.health > 0.0)
+            })
+            .min_by(|(a, a_position, _), (b, b_position, _)| {
+                a_position
+                    .distance(target_position)
+                    .total_cmp(&b_position.distance(target_position))
+                    .then(a.cmp(b))
+            });
+            let Some((next, next_position, _)) = next else {
+                break;
+            };
+
+            hit.push(next);
+            origin = target_position;
+            target_position = next_position;
+            damage *= 1.0 - chain.decay;
+        }
+    }
+}
+
+/// Extra damage multiplier gained per second on the same target
+const BEAM_RAMP_PER_SECOND: f32 = 0.5;
+const BEAM_MAX_RAMP: f32 = 3.0;
+
+/// Beam kept, started or stopped by a turret, applied once every beam has picked its target
+pub struct BeamIntent {
+    turret: Entity,
+    /// `None` when the beam stops
+    target: Option<Entity>,
+    state: BeamState,
+    origin: Vec2,
+    damage: f32,
+    ramp: f32,
+}
+
+pub fn beam_turret_system(
+    time: Res<Time>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(Entity, &Turret, &mut BeamTurret, Option<&Strategy>)>,
+    mut creeps: Query<(Entity, &mut Creep, &Transform, &MovingEntity)>,
+    mut beam_events: MessageWriter<BeamMessage>,
+    mut damage_events: MessageWriter<CreepDamagedMessage>,
+    mut queue: Local<Parallel<Vec<BeamIntent>>>,
+) {
+    let readonly_creeps = creeps.as_readonly();
+    turrets
+        .par_iter_mut()
+        .for_each(|(entity, turret, mut beam, strategy)| {
+            let origin = turret.transform.translation.truncate();
+
+            // Stay on the locked creep while it is alive and in range
+            let locked = beam.target.filter(|target| {
+                readonly_creeps
+                    .get(*target)
+                    .is_ok_and(|(_, creep, transform, _)| {
+                        creep.health > 0.0
+                            && transform.translation.truncate().distance(origin) <= turret.range
+                    })
+            });
+            let target = match locked {
+                Some(target) => Some((target, BeamState::Update)),
+                None => find_top_creeps_within_range(
+                    origin,
+                    0.0,
+                    turret.range,
+                    &index,
+                    &readonly_creeps,
+                    strategy,
+                    1,
+                )
+                .first()
+                .map(|(creep, _, _)| (*creep, BeamState::Start)),
+            };
+
+            let Some((target, state)) = target else {
+                if beam.target.take().is_some() {
+                    queue.scope(|intents| {
+                        intents.push(BeamIntent {
+                            turret: entity,
+                            target: None,
+                            state: BeamState::Stop,
+                            origin,
+                            damage: 0.0,
+                            ramp: 0.0,
+                        })
+                    });
+                }
+                return;
+            };
+            if state == BeamState::Start {
+                beam.target = Some(target);
+                beam.time_on_target = 0.0;
+            } else {
+                beam.time_on_target += time.delta_secs();
+            }
+
+            let ramp = (1.0 + BEAM_RAMP_PER_SECOND * beam.time_on_target).min(BEAM_MAX_RAMP);
+            queue.scope(|intents| {
+                intents.push(BeamIntent {
+                    turret: entity,
+                    target: Some(target),
+                    state,
+                    origin,
+                    damage: turret.damage * ramp * time.delta_secs(),
+                    ramp,
+                })
+            });
+        });
+
+    let mut intents = Vec::new();
+    queue.drain_into(&mut intents);
+    intents.sort_by_key(|intent| intent.turret);
+    for intent in intents {
+        let Some(target) = intent.target else {
+            beam_events.write(BeamMessage {
+                turret: intent.turret,
+                state: BeamState::Stop,
+                origin: intent.origin,
+                target: intent.origin,
+                ramp: 0.0,
+            });
+            continue;
+        };
+        if let Ok((_, mut creep, transform, _)) = creeps.get_mut(target) {
+            damage_creep(
+                &mut damage_events,
+                TurretType::Beam,
+                &mut creep,
+                intent.damage,
+            );
+            beam_events.write(BeamMessage {
+                turret: intent.turret,
+                state: intent.state,
+                origin: intent.origin,
+                target: transform.translation.truncate(),
+                ramp: intent.ramp,
+            });
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn bullet_thrower_system(
     time: Res<Time>,
-    mut turrets: Query<(&mut Turret, &BulletThrower, Option<&Strategy>)>,
-    mut creeps: Query<(Entity, &Creep, &Transform, &MovingEntity)>,
+    index: Res<CreepSpatialIndex>,
+    mut turrets: Query<(Entity, &mut Turret, Option<&Strategy>), With<BulletThrower>>,
+    aims: Query<&TurretAim>,
+    throwers: Query<&BulletThrower>,
+    creeps: Query<(Entity, &Creep, &Transform, &MovingEntity)>,
     mut commands: Commands,
+    mut rolls: DamageRolls,
+    mut queue: Local<Parallel<Vec<FireIntent>>>,
 ) {
-    for (mut turret, bullet_thrower, strategy) in turrets.iter_mut() {
-        shoot_n_creeps!(
-            turret,
-            creeps,
-            time,
-            strategy,
-            2,
-            |creep_entity, creep_position: Vec2, turret_position: Vec2| {
-                commands.spawn((
-                    FollowerBullet {
-                        damage: turret.damage,
-                        target: creep_entity,
-                        speed: bullet_thrower.speed,
-                        direction: (creep_position - turret_position).normalize(),
-                        angular_velocity: 2.0,
-                    },
-                    Transform::from_translation(turret.transform.translation),
-                ));
-            }
-        );
+    for intent in fire_intents(
+        &time,
+        &index,
+        &mut turrets,
+        &aims,
+        &creeps,
+        |_| 0.0,
+        2,
+        &mut queue,
+    ) {
+        if let Ok((_, turret, strategy)) = turrets.get(intent.turret)
+            && let Ok(bullet_thrower) = throwers.get(intent.turret)
+        {
+            let (damage, is_crit) = rolls.roll(intent.turret, turret.damage);
+            commands.spawn((
+                FollowerBullet {
+                    damage,
+                    is_crit,
+                    target: intent.target,
+                    speed: bullet_thrower.speed,
+                    direction: (intent.target_position - intent.turret_position).normalize(),
+                    angular_velocity: 2.0,
+                    strategy: strategy.copied().unwrap_or(Strategy::Closest),
+                    range: turret.range,
+                    time_to_live: FOLLOWER_BULLET_LIFETIME,
+                },
+                Transform::from_translation(turret.transform.translation),
+            ));
+        }
     }
 }
 
-fn time_to_fire(turret: &mut Turret, time: &Res<Time>) -> bool {
+/// Damage rolls of the turrets, drawn in a fixed order so that seeded games replay the same
+#[derive(SystemParam)]
+pub struct DamageRolls<'w, 's> {
+    rng: ResMut<'w, TurretRng>,
+    variances: Query<'w, 's, &'static DamageVariance>,
+}
+
+impl DamageRolls<'_, '_> {
+    /// Damage of one shot of the turret, and whether it is a critical hit
+    pub fn roll(&mut self, turret: Entity, damage: f32) -> (f32, bool) {
+        let Ok(variance) = self.variances.get(turret) else {
+            return (damage, false);
+        };
+        let rng = &mut self.rng.rng;
+        let damage = damage * (1.0 + variance.spread * rng.random_range(-1.0..=1.0));
+        if rng.random::<f32>() < variance.crit_chance {
+            (damage * variance.crit_multiplier, true)
+        } else {
+            (damage, false)
+        }
+    }
+}
+
+fn damage_creep(
+    damage_events: &mut MessageWriter<CreepDamagedMessage>,
+    turret_type: TurretType,
+    creep: &mut Creep,
+    damage: f32,
+) {
+    // Only report the damage actually dealt, overkill does not count
+    let dealt = damage.min(creep.health.max(0.0));
+    creep.health -= damage;
+
+    if dealt > 0.0 {
+        damage_events.write(CreepDamagedMessage {
+            turret_type,
+            damage: dealt,
+        });
+    }
+}
+
+/// Turrets without an aim fire in every direction
+fn is_aligned(aims: &Query<&TurretAim>, turret: Entity) -> bool {
+    aims.get(turret).map_or(true, |aim| aim.aligned)
+}
+
+fn time_to_fire(turret: &mut Turret, time: &Time) -> bool {
     turret.last_fired += time.delta_secs();
 
     if turret.last_fired >= turret.reload_time {
@@ -336,10 +1272,13 @@ fn time_to_fire(turret: &mut Turret, time: &Res<Time>) -> bool {
     false
 }
 
-fn find_top_creeps_within_range(
+/// Best creeps for the strategy between `min_range` and `max_range` of the turret
+fn find_top_creeps_within_range<F: QueryFilter>(
     turret_position: Vec2,
-    turret_range: f32,
-    creeps: &Query<'_, '_, (Entity, &Creep, &Transform, &MovingEntity)>,
+    min_range: f32,
+    max_range: f32,
+    index: &CreepSpatialIndex,
+    creeps: &Query<(Entity, &Creep, &Transform, &MovingEntity), F>,
     strategy: Option<&Strategy>,
     n: usize,
 ) -> Vec<(Entity, Vec2, Vec2)> {
@@ -349,11 +1288,15 @@ fn find_top_creeps_within_range(
         None => &Strategy::Closest,
     };
 
-    for (creep_entity, creep, creep_transform, moving_entity) in creeps.iter() {
-        let creep_position = creep_transform.translation.truncate();
-        let distance = turret_position.distance(creep_position);
+    for (creep_entity, _) in index.within(turret_position, max_range) {
+        // Creeps filtered out of the query, such as already slowed ones, are skipped
+        if let Ok((_, creep, creep_transform, moving_entity)) = creeps.get(creep_entity) {
+            let creep_position = creep_transform.translation.truncate();
+            let distance = turret_position.distance(creep_position);
+            if distance < min_range {
+                continue;
+            }
 
-        if distance <= turret_range {
             let value = match strategy {
                 Strategy::Weakest => -creep.health,
                 Strategy::Strongest => creep.health,
@@ -376,17 +1319,69 @@ fn find_top_creeps_within_range(
         .collect()
 }
 
+/// Bullet close enough to its target to hit it
+pub struct BulletHit {
+    bullet: Entity,
+    target: Entity,
+    target_position: Vec2,
+    damage: f32,
+    is_crit: bool,
+}
+
+/// Seconds a follower bullet keeps chasing creeps before it is removed
+const FOLLOWER_BULLET_LIFETIME: f32 = 5.0;
+
+#[allow(clippy::too_many_arguments)]
 pub fn move_follower_bullets(
     mut commands: Commands,
+    index: Res<CreepSpatialIndex>,
     mut bullets: Query<(Entity, &mut FollowerBullet, &mut Transform), Without<Creep>>,
-    mut creeps: Query<(&Transform, &mut Creep)>,
+    mut creeps: Query<(Entity, &mut Creep, &Transform, &MovingEntity)>,
+    mut damage_events: MessageWriter<CreepDamagedMessage>,
+    mut hit_events: MessageWriter<BulletHitMessage>,
     time: Res<Time>,
+    mut queue: Local<Parallel<Vec<BulletHit>>>,
+    mut expired: Local<Parallel<Vec<Entity>>>,
 ) {
-    for (entity, mut bullet, mut transform) in bullets.iter_mut() {
-        if let Ok((target_transform, mut creep)) = creeps.get_mut(bullet.target) {
-            let target_position = target_transform.translation.truncate();
+    let readonly_creeps = creeps.as_readonly();
+    bullets
+        .par_iter_mut()
+        .for_each(|(entity, mut bullet, mut transform)| {
             let bullet_position = transform.translation.truncate();
+            bullet.time_to_live -= time.delta_secs();
+            if bullet.time_to_live <= 0.0 {
+                expired.scope(|expired| expired.push(entity));
+                return;
+            }
 
+            // The target died, chase another creep the way the turret would pick it
+            let target_alive = readonly_creeps
+                .get(bullet.target)
+                .is_ok_and(|(_, creep, _, _)| creep.health > 0.0);
+            if !target_alive {
+                match find_top_creeps_within_range(
+                    bullet_position,
+                    0.0,
+                    bullet.range,
+                    &index,
+                    &readonly_creeps,
+                    Some(&bullet.strategy),
+                    1,
+                )
+                .first()
+                {
+                    Some((creep, _, _)) => bullet.target = *creep,
+                    None => {
+                        expired.scope(|expired| expired.push(entity));
+                        return;
+                    }
+                }
+            }
+            let Ok((_, _, target_transform, _)) = readonly_creeps.get(bullet.target) else {
+                return;
+            };
+
+            let target_position = target_transform.translation.truncate();
             let direction_to_target = (target_position - bullet_position).normalize();
             let angle_to_target = direction_to_target.y.atan2(direction_to_target.x);
             let current_angle = bullet.direction.y.atan2(bullet.direction.x);
@@ -409,19 +1404,82 @@ pub fn move_follower_bullets(
                 bullet_position + bullet.direction * bullet.speed * time.delta_secs();
             transform.translation = new_position.extend(transform.translation.z);
 
-            if transform.translation.distance(target_transform.translation) < 5.0
-                && creep.health > 0.0
-            {
-                creep.health -= bullet.damage;
-                commands.entity(entity).despawn();
+            if transform.translation.distance(target_transform.translation) < 5.0 {
+                queue.scope(|hits| {
+                    hits.push(BulletHit {
+                        bullet: entity,
+                        target: bullet.target,
+                        target_position,
+                        damage: bullet.damage,
+                        is_crit: bullet.is_crit,
+                    })
+                });
             }
+        });
+
+    let mut hits = Vec::new();
+    queue.drain_into(&mut hits);
+    hits.sort_by_key(|hit| hit.bullet);
+    for hit in hits {
+        // Bullets reaching a creep already killed this tick retarget on the next one
+        if let Ok((_, mut creep, _, _)) = creeps.get_mut(hit.target)
+            && creep.health > 0.0
+        {
+            damage_creep(
+                &mut damage_events,
+                TurretType::Follower,
+                &mut creep,
+                hit.damage,
+            );
+            hit_events.write(BulletHitMessage {
+                target: hit.target_position,
+                is_crit: hit.is_crit,
+            });
+            commands.entity(hit.bullet).despawn();
         }
     }
+    for entity in expired.drain() {
+        commands.entity(entity).despawn();
+    }
 }
 
-pub fn despawn_dead_creeps(mut commands: Commands, mut creeps: Query<(Entity, &Creep)>) {
+pub fn despawn_dead_creeps(
+    mut commands: Commands,
+    mut creeps: Query<(Entity, &Creep)>,
+    mut game_data: ResMut<GameData>,
+    mut killed_events: MessageWriter<CreepKilledMessage>,
+) {
     for (entity, creep) in creeps.iter_mut() {
         if creep.health <= 0.0 {
+            game_data.gold += creep.bounty;
+            game_data.score += 1;
+            killed_events.write(CreepKilledMessage {
+                creep: entity,
+                wave: creep.wave,
+                bounty: creep.bounty,
+            });
+
+            commands.entity(entity).despawn_children();
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn despawn_leaked_creeps(
+    mut commands: Commands,
+    creeps: Query<(Entity, &Creep, &MovingEntity)>,
+    mut game_data: ResMut<GameData>,
+    mut leaked_events: MessageWriter<CreepLeakedMessage>,
+) {
+    for (entity, creep, moving_entity) in creeps.iter() {
+        // A creep without waypoints left has reached the end of the map
+        if moving_entity.waypoints.is_empty() && creep.health > 0.0 {
+            game_data.lives -= 1;
+            leaked_events.write(CreepLeakedMessage {
+                creep: entity,
+                wave: creep.wave,
+            });
+
             commands.entity(entity).despawn_children();
             commands.entity(entity).despawn();
         }
@@ -492,4 +1550,685 @@ mod tests {
         let expected_pos = Vec2::new(15.0, 15.0).move_towards(next_waypoint, fixed_delta * speed);
         assert_eq!(transform, expected_pos); // Check if the position has updated correctly
     }
+
+    #[test]
+    fn test_wave_spawning() {
+        let waves = Waves {
+            waves: vec![
+                Wave {
+                    count: 2,
+                    interval: 1.0,
+                    delay: 3.0,
+                    health: 50.0,
+                    speed: 10.0,
+                    bounty: 5,
+                    health_variance: 0.0,
+                },
+                Wave {
+                    count: 1,
+                    interval: 1.0,
+                    delay: 0.0,
+                    health: 80.0,
+                    speed: 10.0,
+                    bounty: 5,
+                    health_variance: 0.0,
+                },
+            ],
+        };
+        let mut spawn_timer = SpawnTimer::default();
+        let mut rng = CreepRng::from_seed(0);
+
+        let mut spawned_waves = vec![];
+        for _ in 0..50 {
+            spawn_timer.since_last_spawn += 0.1;
+            if let Some((creep, _)) = next_wave_creep(&mut spawn_timer, &waves, &mut rng) {
+                spawned_waves.push((creep.wave, creep.health));
+            }
+        }
+
+        assert_eq!(spawned_waves, vec![(0, 50.0), (0, 50.0), (1, 80.0)]);
+        assert_eq!(spawn_timer.wave, 2);
+    }
+
+    #[test]
+    fn wall_lines_are_atomic() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(
+            crate::TowerDefensePlugin::default()
+                .with_map(crate::config::MapSource::Loaded(crate::MapFile {
+                    kind: crate::MapKind::Free,
+                    map: crate::BaseMap::default(),
+                }))
+                .with_systems(crate::config::EnabledSystems {
+                    spawning: false,
+                    ..default()
+                }),
+        );
+        app.update();
+        let walls = |app: &mut App| {
+            let world = app.world_mut();
+            world.query::<&Turret>().iter(world).count()
+        };
+
+        // A full column would close the path, nothing is placed
+        app.world_mut().write_message(PlaceWallsMessage {
+            from: IVec2::new(5, 0),
+            to: IVec2::new(5, 9),
+        });
+        app.update();
+        assert_eq!(walls(&mut app), 0);
+        assert_eq!(app.world().resource::<GameData>().gold, 500);
+
+        app.world_mut().write_message(PlaceWallsMessage {
+            from: IVec2::new(5, 0),
+            to: IVec2::new(5, 8),
+        });
+        app.update();
+        assert_eq!(walls(&mut app), 9);
+        assert_eq!(app.world().resource::<GameData>().gold, 500 - 9 * 5);
+        let map = app.world().resource::<ActiveMap>();
+        assert!(map.get_path().contains(&IVec2::new(5, 9)));
+    }
+
+    #[test]
+    fn turrets_apply_their_shots_after_targeting() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<CreepSpatialIndex>()
+            .init_resource::<TurretRng>()
+            .add_message::<BasicFireMessage>()
+            .add_message::<CreepDamagedMessage>()
+            .add_systems(
+                Update,
+                (
+                    update_creep_index,
+                    basic_turret_system.after(update_creep_index),
+                ),
+            );
+
+        let creep = app
+            .world_mut()
+            .spawn((
+                Creep {
+                    health: 15.0,
+                    max_health: 15.0,
+                    bounty: 1,
+                    wave: 0,
+                },
+                MovingEntity {
+                    waypoints: vec![],
+                    speed: 0.0,
+                },
+                Transform::from_xyz(20.0, 20.0, 0.0),
+            ))
+            .id();
+        for x in 0..3 {
+            app.world_mut().spawn((
+                Turret {
+                    turret_type: TurretType::Basic,
+                    position: IVec2::new(x, 1),
+                    transform: Transform::from_xyz(x as f32 * 10.0, 10.0, 0.0),
+                    range: 25.0,
+                    damage: 10.0,
+                    reload_time: 1.0,
+                    last_fired: 1.0,
+                },
+                BasicTurret {},
+            ));
+        }
+        app.update();
+
+        // Every turret targeted the creep, the last one only overkills it
+        assert_eq!(app.world().get::<Creep>(creep).unwrap().health, -15.0);
+        let fired = app.world().resource::<Messages<BasicFireMessage>>().len();
+        assert_eq!(fired, 3);
+        let damages = app.world().resource::<Messages<CreepDamagedMessage>>();
+        let dealt: Vec<f32> = damages
+            .iter_current_update_messages()
+            .map(|m| m.damage)
+            .collect();
+        assert_eq!(dealt, vec![10.0, 5.0]);
+
+        let world = app.world_mut();
+        let reloading = world
+            .query::<&Turret>()
+            .iter(world)
+            .all(|t| t.last_fired == 0.0);
+        assert!(reloading);
+    }
+
+    #[test]
+    fn intercept_leads_moving_targets() {
+        assert_eq!(
+            intercept(Vec2::ZERO, 30.0, Vec2::new(50.0, 0.0), Vec2::ZERO),
+            Some(Vec2::new(50.0, 0.0))
+        );
+
+        let target = Vec2::new(50.0, 0.0);
+        let velocity = Vec2::new(0.0, 10.0);
+        let aim = intercept(Vec2::ZERO, 30.0, target, velocity).unwrap();
+        let time = (aim - target).length() / velocity.length();
+        assert!((aim.length() - 30.0 * time).abs() < 1e-3);
+
+        // Too slow to catch a creep running away
+        assert_eq!(
+            intercept(Vec2::ZERO, 5.0, target, Vec2::new(10.0, 0.0)),
+            None
+        );
+    }
+
+    fn projectile_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<CreepSpatialIndex>()
+            .init_resource::<TurretRng>()
+            .add_message::<CreepDamagedMessage>()
+            .add_message::<BulletHitMessage>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                0.05,
+            )))
+            .add_systems(
+                Update,
+                (
+                    move_creeps,
+                    update_creep_index,
+                    projectile_turret_system,
+                    move_projectiles,
+                )
+                    .chain(),
+            );
+
+        let creep = app
+            .world_mut()
+            .spawn((
+                Creep {
+                    health: 10.0,
+                    max_health: 10.0,
+                    bounty: 1,
+                    wave: 0,
+                },
+                MovingEntity {
+                    waypoints: vec![Vec2::new(30.0, 100.0)],
+                    speed: 10.0,
+                },
+                Transform::from_xyz(30.0, 0.0, 0.0),
+            ))
+            .id();
+        app.world_mut().spawn((
+            Turret {
+                turret_type: TurretType::Basic,
+                position: IVec2::ZERO,
+                transform: Transform::default(),
+                range: 50.0,
+                damage: 10.0,
+                // A single shot
+                reload_time: 100.0,
+                last_fired: 100.0,
+            },
+            BasicTurret {},
+            ProjectileLauncher { speed: 40.0 },
+        ));
+        app.update();
+        (app, creep)
+    }
+
+    fn projectiles(app: &mut App) -> usize {
+        let world = app.world_mut();
+        world.query::<&Projectile>().iter(world).count()
+    }
+
+    #[test]
+    fn projectiles_hit_on_impact() {
+        let (mut app, creep) = projectile_app();
+        assert_eq!(projectiles(&mut app), 1);
+        assert_eq!(app.world().get::<Creep>(creep).unwrap().health, 10.0);
+
+        // The projectile was aimed ahead of the creep
+        for _ in 0..40 {
+            app.update();
+        }
+        assert_eq!(app.world().get::<Creep>(creep).unwrap().health, 0.0);
+        assert_eq!(projectiles(&mut app), 0);
+    }
+
+    #[test]
+    fn projectiles_miss_dead_targets() {
+        let (mut app, creep) = projectile_app();
+        app.world_mut().get_mut::<Creep>(creep).unwrap().health = 0.0;
+
+        // Flying on until it expires
+        for _ in 0..20 {
+            app.update();
+        }
+        assert_eq!(projectiles(&mut app), 1);
+        for _ in 0..30 {
+            app.update();
+        }
+        assert_eq!(projectiles(&mut app), 0);
+        assert_eq!(app.world().get::<Creep>(creep).unwrap().health, 0.0);
+    }
+
+    fn spawn_still_creep(app: &mut App, position: Vec2) -> Entity {
+        app.world_mut()
+            .spawn((
+                Creep {
+                    health: 10.0,
+                    max_health: 10.0,
+                    bounty: 1,
+                    wave: 0,
+                },
+                MovingEntity {
+                    waypoints: vec![],
+                    speed: 0.0,
+                },
+                Transform::from_translation(position.extend(0.0)),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn follower_bullets_retarget_and_expire() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<CreepSpatialIndex>()
+            .init_resource::<TurretRng>()
+            .add_message::<CreepDamagedMessage>()
+            .add_message::<BulletHitMessage>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                0.05,
+            )))
+            .add_systems(Update, (update_creep_index, move_follower_bullets).chain());
+
+        let first = spawn_still_creep(&mut app, Vec2::new(20.0, 0.0));
+        let second = spawn_still_creep(&mut app, Vec2::new(-20.0, 0.0));
+        let bullet = |target, angular_velocity, time_to_live| {
+            (
+                FollowerBullet {
+                    direction: Vec2::X,
+                    target,
+                    damage: 5.0,
+                    is_crit: false,
+                    speed: 30.0,
+                    angular_velocity,
+                    strategy: Strategy::Closest,
+                    range: 100.0,
+                    time_to_live,
+                },
+                Transform::default(),
+            )
+        };
+        let chaser = app.world_mut().spawn(bullet(first, 10.0, 5.0)).id();
+        // Never turns towards its target
+        let lost = app.world_mut().spawn(bullet(second, 0.0, 0.5)).id();
+
+        app.world_mut().despawn(first);
+        app.update();
+        assert_eq!(
+            app.world().get::<FollowerBullet>(chaser).unwrap().target,
+            second
+        );
+
+        for _ in 0..40 {
+            app.update();
+        }
+        assert!(app.world().get_entity(lost).is_err());
+        assert!(app.world().get_entity(chaser).is_err());
+        assert_eq!(app.world().get::<Creep>(second).unwrap().health, 5.0);
+
+        // Nothing left to chase
+        let orphan = app.world_mut().spawn(bullet(second, 10.0, 5.0)).id();
+        app.world_mut().despawn(second);
+        app.update();
+        assert!(app.world().get_entity(orphan).is_err());
+    }
+
+    #[test]
+    fn beam_damage_ramps_up_on_the_same_creep() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<CreepSpatialIndex>()
+            .init_resource::<TurretRng>()
+            .add_message::<CreepDamagedMessage>()
+            .add_message::<BeamMessage>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                0.1,
+            )))
+            .add_systems(Update, (update_creep_index, beam_turret_system).chain());
+
+        let creep = spawn_still_creep(&mut app, Vec2::new(10.0, 0.0));
+        app.world_mut().get_mut::<Creep>(creep).unwrap().health = 1000.0;
+        let turret = app
+            .world_mut()
+            .spawn((
+                Turret {
+                    turret_type: TurretType::Beam,
+                    position: IVec2::ZERO,
+                    transform: Transform::default(),
+                    range: 35.0,
+                    damage: 10.0,
+                    reload_time: 0.0,
+                    last_fired: 0.0,
+                },
+                BeamTurret::default(),
+            ))
+            .id();
+        let beam_states = |app: &App| {
+            app.world()
+                .resource::<Messages<BeamMessage>>()
+                .iter_current_update_messages()
+                .map(|message| message.state)
+                .collect::<Vec<_>>()
+        };
+        let health = |app: &App| app.world().get::<Creep>(creep).unwrap().health;
+
+        // The first frame has no time to deal damage
+        app.update();
+        assert_eq!(beam_states(&app), vec![BeamState::Start]);
+        let mut last = health(&app);
+        let mut dealt = Vec::new();
+        for _ in 0..50 {
+            app.update();
+            dealt.push(last - health(&app));
+            last = health(&app);
+        }
+        assert_eq!(beam_states(&app), vec![BeamState::Update]);
+        assert!(dealt[5] > dealt[0]);
+        // Capped once the ramp is reached
+        assert!((dealt[49] - 10.0 * BEAM_MAX_RAMP * 0.1).abs() < 1e-3);
+        assert_eq!(
+            app.world().get::<BeamTurret>(turret).unwrap().target,
+            Some(creep)
+        );
+
+        app.world_mut().despawn(creep);
+        app.update();
+        assert_eq!(beam_states(&app), vec![BeamState::Stop]);
+        assert_eq!(app.world().get::<BeamTurret>(turret).unwrap().target, None);
+    }
+
+    #[test]
+    fn chain_hits_each_creep_once_with_decaying_damage() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<CreepSpatialIndex>()
+            .init_resource::<TurretRng>()
+            .add_message::<CreepDamagedMessage>()
+            .add_message::<BasicFireMessage>()
+            .add_systems(Update, (update_creep_index, chain_turret_system).chain());
+
+        let creeps: Vec<Entity> = [10.0, 20.0, 30.0, 200.0]
+            .into_iter()
+            .map(|x| {
+                let creep = spawn_still_creep(&mut app, Vec2::new(x, 0.0));
+                app.world_mut().get_mut::<Creep>(creep).unwrap().health = 100.0;
+                creep
+            })
+            .collect();
+        app.world_mut().spawn((
+            Turret {
+                turret_type: TurretType::Chain,
+                position: IVec2::ZERO,
+                transform: Transform::default(),
+                range: 30.0,
+                damage: 10.0,
+                reload_time: 1.5,
+                last_fired: 1.5,
+            },
+            ChainTurret {
+                bounces: 3,
+                bounce_radius: 15.0,
+                decay: 0.5,
+            },
+        ));
+        app.update();
+
+        let mut dealt: Vec<f32> = creeps
+            .iter()
+            .map(|creep| 100.0 - app.world().get::<Creep>(*creep).unwrap().health)
+            .collect();
+        // The far creep is out of every bounce
+        assert_eq!(dealt.pop(), Some(0.0));
+        dealt.sort_by(f32::total_cmp);
+        assert_eq!(dealt, vec![2.5, 5.0, 10.0]);
+        assert_eq!(
+            app.world().resource::<Messages<BasicFireMessage>>().len(),
+            3
+        );
+    }
+
+    #[test]
+    fn predicted_positions_follow_the_waypoints() {
+        let moving_entity = MovingEntity {
+            waypoints: vec![Vec2::new(10.0, 10.0), Vec2::new(10.0, 0.0)],
+            speed: 10.0,
+        };
+        assert_eq!(
+            predict_position(Vec2::ZERO, &moving_entity, None, 1.5),
+            Vec2::new(10.0, 5.0)
+        );
+        // Stops at the end of the path
+        assert_eq!(
+            predict_position(Vec2::ZERO, &moving_entity, None, 10.0),
+            Vec2::new(10.0, 10.0)
+        );
+    }
+
+    #[test]
+    fn mortar_shells_land_where_creeps_will_be() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<CreepSpatialIndex>()
+            .init_resource::<TurretRng>()
+            .add_message::<CreepDamagedMessage>()
+            .add_message::<BasicFireMessage>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                0.1,
+            )))
+            .add_systems(
+                Update,
+                (
+                    move_creeps,
+                    update_creep_index,
+                    mortar_turret_system,
+                    move_mortar_shells,
+                )
+                    .chain(),
+            );
+
+        // Too close to be shelled
+        let close = spawn_still_creep(&mut app, Vec2::new(10.0, 0.0));
+        let walking = spawn_still_creep(&mut app, Vec2::new(40.0, 0.0));
+        app.world_mut().entity_mut(walking).insert(MovingEntity {
+            waypoints: vec![Vec2::new(100.0, 0.0)],
+            speed: 10.0,
+        });
+        app.world_mut().spawn((
+            Turret {
+                turret_type: TurretType::Mortar,
+                position: IVec2::ZERO,
+                transform: Transform::default(),
+                range: 80.0,
+                damage: 5.0,
+                reload_time: 3.0,
+                last_fired: 3.0,
+            },
+            MortarTurret::default(),
+        ));
+        app.update();
+
+        let shell = {
+            let world = app.world_mut();
+            world.query::<&MortarShell>().single(world).unwrap().clone()
+        };
+        assert_eq!(shell.target, Vec2::new(55.0, 0.0));
+        let health = |app: &App, creep| app.world().get::<Creep>(creep).unwrap().health;
+        assert_eq!(health(&app, walking), 10.0);
+
+        for _ in 0..16 {
+            app.update();
+        }
+        assert_eq!(health(&app, walking), 5.0);
+        assert_eq!(health(&app, close), 10.0);
+        let world = app.world_mut();
+        assert_eq!(world.query::<&MortarShell>().iter(world).count(), 0);
+    }
+
+    #[test]
+    fn invalid_map_files_fall_back_to_the_default_map() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(
+            crate::TowerDefensePlugin::default()
+                .with_map(crate::config::MapSource::File("missing/map.json".into())),
+        );
+        app.update();
+        assert!(app.world().resource::<ActiveMap>().as_dynamic().is_none());
+    }
+
+    #[test]
+    fn auras_buff_the_turrets_around_them() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(
+            crate::TowerDefensePlugin::default()
+                .with_map(crate::config::MapSource::Loaded(crate::MapFile {
+                    kind: crate::MapKind::Free,
+                    map: crate::BaseMap::default(),
+                }))
+                .with_systems(crate::config::EnabledSystems {
+                    spawning: false,
+                    ..default()
+                }),
+        );
+        app.update();
+        let place = |app: &mut App, turret_type, position| {
+            app.world_mut().write_message(PlaceTurretMessage {
+                turret_type,
+                position,
+            });
+            app.update();
+        };
+        let basic = |app: &mut App| {
+            let world = app.world_mut();
+            world
+                .query_filtered::<&Turret, With<BasicTurret>>()
+                .single(world)
+                .unwrap()
+                .clone()
+        };
+
+        place(&mut app, TurretType::Basic, IVec2::new(5, 5));
+        assert_eq!(basic(&mut app).damage, 10.0);
+
+        // Placed after the turret it buffs
+        place(&mut app, TurretType::Aura, IVec2::new(6, 5));
+        let buffed = basic(&mut app);
+        assert_eq!(buffed.damage, 12.0);
+        assert_eq!(buffed.range, 27.5);
+        assert_eq!(buffed.reload_time, 1.0 / 1.15);
+
+        let aura = {
+            let world = app.world_mut();
+            world
+                .query_filtered::<Entity, With<AuraTurret>>()
+                .single(world)
+                .unwrap()
+        };
+        app.world_mut().despawn(aura);
+        app.update();
+        assert_eq!(basic(&mut app).damage, 10.0);
+        assert_eq!(basic(&mut app).range, 25.0);
+    }
+
+    #[test]
+    fn turrets_only_fire_once_turned_to_their_target() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<CreepSpatialIndex>()
+            .init_resource::<TurretRng>()
+            .add_message::<CreepDamagedMessage>()
+            .add_message::<BasicFireMessage>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                0.1,
+            )))
+            .add_systems(
+                Update,
+                (update_creep_index, aim_turrets, basic_turret_system).chain(),
+            );
+
+        let creep = spawn_still_creep(&mut app, Vec2::new(10.0, 0.0));
+        app.world_mut().get_mut::<Creep>(creep).unwrap().health = 1000.0;
+        let turret = app
+            .world_mut()
+            .spawn((
+                Turret {
+                    turret_type: TurretType::Basic,
+                    position: IVec2::ZERO,
+                    transform: Transform::default(),
+                    range: 35.0,
+                    damage: 10.0,
+                    reload_time: 0.0,
+                    last_fired: 0.0,
+                },
+                BasicTurret {},
+                // Facing away from the creep
+                TurretAim {
+                    angle: f32::consts::PI,
+                    turn_rate: 4.0,
+                    aligned: false,
+                },
+            ))
+            .id();
+        let health = |app: &App| app.world().get::<Creep>(creep).unwrap().health;
+
+        // Half a turn takes a bit less than 8 steps of 0.4 radians
+        for _ in 0..8 {
+            app.update();
+        }
+        assert_eq!(health(&app), 1000.0);
+        let aim = app.world().get::<TurretAim>(turret).unwrap();
+        assert!(!aim.aligned);
+        assert!(aim.angle.abs() < f32::consts::PI);
+
+        app.update();
+        app.update();
+        assert!(health(&app) < 1000.0);
+        let aim = app.world().get::<TurretAim>(turret).unwrap();
+        assert!(aim.aligned);
+        assert!(aim.angle.abs() <= AIM_TOLERANCE);
+    }
+
+    #[test]
+    fn damage_rolls_replay_with_the_seed() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let rolls = |seed| {
+            let mut world = World::new();
+            world.insert_resource(TurretRng::from_seed(seed));
+            let turret = world
+                .spawn(DamageVariance {
+                    spread: 0.1,
+                    crit_chance: 0.5,
+                    crit_multiplier: 2.0,
+                })
+                .id();
+            let flat = world.spawn_empty().id();
+            world
+                .run_system_once(move |mut rolls: DamageRolls| {
+                    assert_eq!(rolls.roll(flat, 10.0), (10.0, false));
+                    (0..50)
+                        .map(|_| rolls.roll(turret, 10.0))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap()
+        };
+
+        let first = rolls(7);
+        assert_eq!(first, rolls(7));
+        assert_ne!(first, rolls(8));
+        assert!(first.iter().any(|(_, is_crit)| *is_crit));
+        assert!(first.iter().any(|(_, is_crit)| !*is_crit));
+        for (damage, is_crit) in first {
+            let base = if is_crit { damage / 2.0 } else { damage };
+            assert!((9.0..=11.0).contains(&base));
+        }
+    }
 }
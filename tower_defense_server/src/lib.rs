@@ -1,4 +1,4 @@
-use std::net::UdpSocket;
+use std::net::{SocketAddr, UdpSocket};
 use std::time::SystemTime;
 
 use bevy::prelude::*;
@@ -10,19 +10,31 @@ use bevy_renet::renet::{ClientId, ConnectionConfig, DefaultChannel, RenetServer,
 
 use protos::protos::messages::*;
 
-pub struct ServerPlugin;
+pub struct ServerPlugin {
+    pub bind: SocketAddr,
+    pub max_clients: usize,
+}
+
+impl Default for ServerPlugin {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0:5000".parse().unwrap(),
+            max_clients: 64,
+        }
+    }
+}
 
 impl Plugin for ServerPlugin {
     fn build(&self, app: &mut App) {
         let server = RenetServer::new(ConnectionConfig::default());
 
-        let server_addr = "0.0.0.0:5000".parse().unwrap();
+        let server_addr = self.bind;
         let socket = UdpSocket::bind(server_addr).unwrap();
         let server_config = ServerConfig {
             current_time: SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap(),
-            max_clients: 64,
+            max_clients: self.max_clients,
             protocol_id: 0,
             public_addresses: vec![server_addr],
             authentication: ServerAuthentication::Unsecure,